@@ -1,6 +1,7 @@
 use std::{
     io::{self, Write},
     path::PathBuf,
+    process::ExitCode,
 };
 
 use clap::Parser;
@@ -13,34 +14,44 @@ pub struct Cli {
 }
 
 impl Cli {
-    pub fn run() {
-        println!("Welcome to the Rust Lox 0.1.0 interpreter.");
+    pub fn run() -> ExitCode {
         let cli = Cli::parse();
         let lox = rustlox::RustLox::new();
         // Run with File
         if let Some(file_path) = cli.file_path.as_deref() {
-            lox.run_with_file(file_path);
-            return;
+            return lox.run_with_file(file_path);
         }
         // Run with prompt
+        println!("Welcome to the Rust Lox 0.1.0 interpreter.");
         Self::readline(&lox);
+        ExitCode::SUCCESS
     }
 
+    /// Reads and runs entries until end of input. An entry continues over
+    /// several lines, prompted with `...`, while it has unclosed brackets.
     fn readline(lox: &rustlox::RustLox) {
+        let mut entry = String::new();
         loop {
-            print!(">>> ");
+            print!("{}", if entry.is_empty() { ">>> " } else { "... " });
             io::stdout().flush().expect("Failed to flush");
 
-            let mut input = String::new();
-            io::stdin()
-                .read_line(&mut input)
-                .expect("Failed to read line");
+            let Some(line) = lox.read_line().expect("Failed to read line") else {
+                // End of input (Ctrl-D): leave the shell's prompt on a new line.
+                println!();
+                break;
+            };
+            entry.push_str(&line);
+            entry.push('\n');
 
-            if input.is_empty() {
+            if entry.trim().is_empty() {
+                entry.clear();
+                continue;
+            }
+            if rustlox::RustLox::is_incomplete(&entry) {
                 continue;
             }
 
-            lox.run_with_prompt(input.clone());
+            lox.run_with_prompt(std::mem::take(&mut entry));
         }
     }
 }
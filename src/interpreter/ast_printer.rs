@@ -1,14 +1,16 @@
 use super::{
-    expression::{Expr, ExprVisitor},
+    expression::{Depth, Expr, ExprVisitor},
     token::Token,
     token_type::Literal,
 };
 
+/// Renders expressions in a fully parenthesized prefix form, which shows
+/// how the parser grouped them.
 pub struct AstPrinter {}
 
 impl AstPrinter {
-    pub fn print(&self, expr: &Expr) {
-        println!("{}", expr.accept(self));
+    pub fn print(&self, expr: &Expr) -> String {
+        expr.accept(self)
     }
     fn parenthesize(&self, name: String, exprs: Vec<&Expr>) -> String {
         let exprs_str: Vec<String> = exprs.iter().map(|expr| expr.accept(self)).collect();
@@ -18,28 +20,95 @@ impl AstPrinter {
 
 impl ExprVisitor for AstPrinter {
     type Res = String;
-    fn visit_binary(&self, left: &Box<Expr>, operator: &Token, right: &Box<Expr>) -> Self::Res {
+    fn visit_assign(&self, name: &Token, value: &Expr, _depth: &Depth) -> Self::Res {
+        self.parenthesize(format!("= {}", name.lexeme), vec![value])
+    }
+
+    fn visit_binary(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::Res {
         self.parenthesize(operator.lexeme.clone(), vec![left, right])
     }
 
-    fn visit_grouping(&self, expr: &Box<Expr>) -> Self::Res {
+    fn visit_call(&self, callee: &Expr, _paren: &Token, arguments: &[Expr]) -> Self::Res {
+        let mut exprs = vec![callee];
+        exprs.extend(arguments);
+        self.parenthesize("call".to_string(), exprs)
+    }
+
+    fn visit_get(&self, object: &Expr, name: &Token) -> Self::Res {
+        self.parenthesize(format!(". {}", name.lexeme), vec![object])
+    }
+
+    fn visit_grouping(&self, expr: &Expr) -> Self::Res {
         self.parenthesize("grouping".to_string(), vec![expr])
     }
 
+    fn visit_interpolation(&self, parts: &[Expr]) -> Self::Res {
+        self.parenthesize("interpolation".to_string(), parts.iter().collect())
+    }
+
     fn visit_literal(&self, literal: &Literal) -> Self::Res {
         match literal {
             Literal::Bool(b) => b.to_string(),
-            Literal::Float(f) => f.to_string(),
+            Literal::Float(f) => format!("{:?}", f),
             Literal::Integer(i) => i.to_string(),
-            Literal::None => "none".to_string(),
-            Literal::String(string) => string.clone(),
+            Literal::None => "nil".to_string(),
+            Literal::String(string) => format!("{:?}", string),
         }
     }
 
-    fn visit_unary(&self, operator: &Token, expr: &Box<Expr>) -> Self::Res {
+    fn visit_logical(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::Res {
+        self.parenthesize(operator.lexeme.clone(), vec![left, right])
+    }
+
+    fn visit_set(&self, object: &Expr, name: &Token, value: &Expr) -> Self::Res {
+        self.parenthesize(format!("= .{}", name.lexeme), vec![object, value])
+    }
+
+    fn visit_super(&self, keyword: &Token, method: &Token, _depth: &Depth) -> Self::Res {
+        format!("{}.{}", keyword.lexeme, method.lexeme)
+    }
+
+    fn visit_this(&self, keyword: &Token, _depth: &Depth) -> Self::Res {
+        keyword.lexeme.clone()
+    }
+
+    fn visit_unary(&self, operator: &Token, expr: &Expr) -> Self::Res {
         self.parenthesize(operator.lexeme.clone(), vec![expr])
     }
-    fn visit_variable(&self, name: &Token) -> Self::Res {
-        self.parenthesize(name.lexeme.clone(), vec![])
+    fn visit_variable(&self, name: &Token, _depth: &Depth) -> Self::Res {
+        name.lexeme.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{parser::Parser, scanner::Scanner, statement::Statement};
+
+    /// Parses `source`, a single expression statement, and prints it.
+    fn print(source: &str) -> String {
+        let tokens = Scanner::new(source.to_string()).scan().unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+        match &statements[..] {
+            [Statement::Expression(expr, _)] => AstPrinter {}.print(expr),
+            _ => panic!("expected one expression statement in {:?}", source),
+        }
+    }
+
+    #[test]
+    fn prints_every_kind_of_expression() {
+        assert_eq!(
+            print("a = -b * (c + 1.5) ** 2;"),
+            "(= a (* (- b) (** (grouping (+ c 1.5)) 2)))"
+        );
+        assert_eq!(print("x or y and !nil;"), "(or x (and y (! nil)))");
+        assert_eq!(
+            print("this.f(1, \"s\").g = super.h;"),
+            "(= .g (call (. f this) 1 \"s\") super.h)"
+        );
+        assert_eq!(
+            print("\"n: ${n + 1}!\";"),
+            "(interpolation \"n: \" (+ n 1) \"!\")"
+        );
     }
 }
@@ -0,0 +1,131 @@
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use super::{
+    class::LoxInstance,
+    environment::Environment,
+    runtime_error::{RuntimeError, Unwind},
+    statement::FunctionDecl,
+    value::Value,
+    Interpreter,
+};
+
+/// Anything that can be invoked with `callee(arguments)`.
+pub trait LoxCallable {
+    fn arity(&self) -> usize;
+    fn call(&self, interpreter: &Interpreter, arguments: Vec<Value>)
+        -> Result<Value, RuntimeError>;
+}
+
+pub struct LoxFunction {
+    declaration: Rc<FunctionDecl>,
+    /// The environment that was live where the function was declared, shared
+    /// so that the function keeps seeing later updates to captured variables.
+    closure: Rc<RefCell<Environment>>,
+    /// Set for a class's `init` method, which always returns `this`.
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: Rc<FunctionDecl>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        LoxFunction {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    /// Returns a copy of this method whose closure binds `this` to `instance`.
+    pub fn bind(&self, instance: Rc<LoxInstance>) -> LoxFunction {
+        let environment = Environment::new(Some(Rc::clone(&self.closure)));
+        environment.define("this".to_string(), Value::Instance(instance));
+        LoxFunction::new(
+            Rc::clone(&self.declaration),
+            Rc::new(RefCell::new(environment)),
+            self.is_initializer,
+        )
+    }
+
+    fn bound_this(&self) -> Value {
+        self.closure
+            .borrow()
+            .get_local("this")
+            .unwrap_or(Value::Nil)
+    }
+}
+
+impl LoxCallable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(
+        &self,
+        interpreter: &Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let environment = Environment::new(Some(Rc::clone(&self.closure)));
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(param.lexeme.clone(), argument);
+        }
+
+        match interpreter.execute_block(&self.declaration.body, environment) {
+            Ok(()) | Err(Unwind::Return(_)) if self.is_initializer => Ok(self.bound_this()),
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(error)) => Err(error),
+        }
+    }
+}
+
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
+
+/// The Rust body of a native function. It receives exactly `arity` arguments.
+pub type NativeBody = dyn Fn(&Interpreter, &[Value]) -> Result<Value, RuntimeError>;
+
+/// A function implemented in Rust and exposed to Lox as a global.
+pub struct NativeFunction {
+    pub name: String,
+    arity: usize,
+    body: Box<NativeBody>,
+}
+
+impl NativeFunction {
+    pub fn new<F>(name: &str, arity: usize, body: F) -> Self
+    where
+        F: Fn(&Interpreter, &[Value]) -> Result<Value, RuntimeError> + 'static,
+    {
+        NativeFunction {
+            name: name.to_string(),
+            arity,
+            body: Box::new(body),
+        }
+    }
+}
+
+impl LoxCallable for NativeFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        &self,
+        interpreter: &Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        (self.body)(interpreter, &arguments)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
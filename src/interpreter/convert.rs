@@ -0,0 +1,252 @@
+use std::{cell::RefCell, collections::HashMap, fmt, hash::BuildHasher, rc::Rc};
+
+use super::{runtime_error::RuntimeError, value::Value};
+
+/// Conversion from a Rust value into a Lox value.
+pub trait IntoLox {
+    fn into_lox(self) -> Value;
+}
+
+/// Conversion from a Lox value into a Rust value, failing if the value has
+/// the wrong type.
+pub trait FromLox: Sized {
+    fn from_lox(value: &Value) -> Result<Self, ConversionError>;
+}
+
+/// Why a Lox value could not be converted to the requested Rust type.
+#[derive(Debug, Clone)]
+pub struct ConversionError {
+    pub message: String,
+}
+
+impl ConversionError {
+    fn mismatch(expected: &str, found: &Value) -> Self {
+        ConversionError {
+            message: format!("Expected {} but got {}.", expected, found.type_name()),
+        }
+    }
+
+    /// Says where in a list or map the failing value was, e.g. `at index 2`.
+    fn within(self, location: String) -> Self {
+        ConversionError {
+            message: format!("{}: {}", location, self.message),
+        }
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// Lets native functions use `?` on conversions of their arguments.
+impl From<ConversionError> for RuntimeError {
+    fn from(error: ConversionError) -> Self {
+        RuntimeError::native(&error.message)
+    }
+}
+
+impl IntoLox for Value {
+    fn into_lox(self) -> Value {
+        self
+    }
+}
+
+impl FromLox for Value {
+    fn from_lox(value: &Value) -> Result<Self, ConversionError> {
+        Ok(value.clone())
+    }
+}
+
+impl IntoLox for i64 {
+    fn into_lox(self) -> Value {
+        Value::Integer(self)
+    }
+}
+
+impl FromLox for i64 {
+    fn from_lox(value: &Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::Integer(val) => Ok(*val),
+            _ => Err(ConversionError::mismatch("integer", value)),
+        }
+    }
+}
+
+impl IntoLox for f64 {
+    fn into_lox(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+/// Integers are accepted too, matching how arithmetic promotes them.
+impl FromLox for f64 {
+    fn from_lox(value: &Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::Float(val) => Ok(*val),
+            Value::Integer(val) => Ok(*val as f64),
+            _ => Err(ConversionError::mismatch("number", value)),
+        }
+    }
+}
+
+impl IntoLox for bool {
+    fn into_lox(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl FromLox for bool {
+    fn from_lox(value: &Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::Bool(val) => Ok(*val),
+            _ => Err(ConversionError::mismatch("bool", value)),
+        }
+    }
+}
+
+impl IntoLox for String {
+    fn into_lox(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl IntoLox for &str {
+    fn into_lox(self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl FromLox for String {
+    fn from_lox(value: &Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::String(val) => Ok(val.clone()),
+            _ => Err(ConversionError::mismatch("string", value)),
+        }
+    }
+}
+
+/// `None` is `nil`.
+impl<T: IntoLox> IntoLox for Option<T> {
+    fn into_lox(self) -> Value {
+        self.map_or(Value::Nil, IntoLox::into_lox)
+    }
+}
+
+impl<T: FromLox> FromLox for Option<T> {
+    fn from_lox(value: &Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::Nil => Ok(None),
+            _ => T::from_lox(value).map(Some),
+        }
+    }
+}
+
+impl<T: IntoLox> IntoLox for Vec<T> {
+    fn into_lox(self) -> Value {
+        let items = self.into_iter().map(IntoLox::into_lox).collect();
+        Value::List(Rc::new(RefCell::new(items)))
+    }
+}
+
+impl<T: FromLox> FromLox for Vec<T> {
+    fn from_lox(value: &Value) -> Result<Self, ConversionError> {
+        let Value::List(list) = value else {
+            return Err(ConversionError::mismatch("list", value));
+        };
+        list.borrow()
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                T::from_lox(item).map_err(|e| e.within(format!("At index {}", index)))
+            })
+            .collect()
+    }
+}
+
+impl<T: IntoLox, S> IntoLox for HashMap<String, T, S> {
+    fn into_lox(self) -> Value {
+        let entries = self
+            .into_iter()
+            .map(|(key, value)| (key, value.into_lox()))
+            .collect();
+        Value::Map(Rc::new(RefCell::new(entries)))
+    }
+}
+
+impl<T: FromLox, S: BuildHasher + Default> FromLox for HashMap<String, T, S> {
+    fn from_lox(value: &Value) -> Result<Self, ConversionError> {
+        let Value::Map(map) = value else {
+            return Err(ConversionError::mismatch("map", value));
+        };
+        map.borrow()
+            .iter()
+            .map(|(key, item)| {
+                T::from_lox(item)
+                    .map(|item| (key.clone(), item))
+                    .map_err(|e| e.within(format!("At key '{}'", key)))
+            })
+            .collect()
+    }
+}
+
+/// Arguments for calling a Lox function from Rust: a tuple of `IntoLox`
+/// values, or a `Vec` of them.
+pub trait IntoLoxArgs {
+    fn into_lox_args(self) -> Vec<Value>;
+}
+
+impl<T: IntoLox> IntoLoxArgs for Vec<T> {
+    fn into_lox_args(self) -> Vec<Value> {
+        self.into_iter().map(IntoLox::into_lox).collect()
+    }
+}
+
+/// A Rust closure that can be defined as a Lox function with `define_fn`.
+/// It takes `FromLox` arguments, whose types make up the tuple `Args`, and
+/// returns an `IntoLox` value or a runtime error.
+pub trait NativeFn<Args>: 'static {
+    const ARITY: usize;
+    /// Converts `arguments`, which hold exactly `ARITY` values, and calls
+    /// the closure with them.
+    fn call_with(&self, arguments: &[Value]) -> Result<Value, RuntimeError>;
+}
+
+macro_rules! tuple_impls {
+    ( $arity:expr; $( $arg:ident $index:tt ),* ) => {
+        impl<$( $arg: IntoLox ),*> IntoLoxArgs for ( $( $arg, )* ) {
+            fn into_lox_args(self) -> Vec<Value> {
+                vec![$( self.$index.into_lox() ),*]
+            }
+        }
+
+        impl<Body, R, $( $arg ),*> NativeFn<( $( $arg, )* )> for Body
+        where
+            Body: Fn($( $arg ),*) -> Result<R, RuntimeError> + 'static,
+            R: IntoLox,
+            $( $arg: FromLox, )*
+        {
+            const ARITY: usize = $arity;
+
+            #[allow(unused_variables)]
+            fn call_with(&self, arguments: &[Value]) -> Result<Value, RuntimeError> {
+                let result = self($(
+                    $arg::from_lox(&arguments[$index])
+                        .map_err(|e| e.within(format!("Argument {}", $index + 1)))?
+                ),*);
+                result.map(IntoLox::into_lox)
+            }
+        }
+    };
+}
+
+tuple_impls!(0;);
+tuple_impls!(1; A 0);
+tuple_impls!(2; A 0, B 1);
+tuple_impls!(3; A 0, B 1, C 2);
+tuple_impls!(4; A 0, B 1, C 2, D 3);
+tuple_impls!(5; A 0, B 1, C 2, D 3, E 4);
+tuple_impls!(6; A 0, B 1, C 2, D 3, E 4, G 5);
@@ -1,10 +1,10 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use super::{token::Token, token_type::Literal};
+use super::{runtime_error::RuntimeError, token::Token, value::Value};
 
 pub struct Environment {
     enclosing: Option<Rc<RefCell<Environment>>>,
-    values: RefCell<HashMap<String, Literal>>,
+    values: RefCell<HashMap<String, Value>>,
 }
 
 impl Environment {
@@ -15,29 +15,75 @@ impl Environment {
         }
     }
 
-    pub fn get(&self, name: &Token) -> Result<Literal, String> {
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
         if let Some(value) = self.values.borrow().get(&name.lexeme).cloned() {
             return Ok(value);
         }
         if let Some(enclosing) = &self.enclosing {
-            return enclosing.borrow().get(&name);
+            return enclosing.borrow().get(name);
         }
-        Err(format!("Undefined variable '{}'.", name.lexeme))
+        Err(RuntimeError::new(
+            name,
+            &format!("Undefined variable '{}'.", name.lexeme),
+        ))
     }
 
-    pub fn define(&self, name: String, value: Literal) {
+    /// Looks `name` up in this environment only, ignoring enclosing ones.
+    pub fn get_local(&self, name: &str) -> Option<Value> {
+        self.values.borrow().get(name).cloned()
+    }
+
+    pub fn define(&self, name: String, value: Value) {
         self.values.borrow_mut().insert(name, value);
     }
 
-    pub fn assign(&self, name: &Token, value: Literal) -> Result<(), String> {
+    pub fn assign(&self, name: &Token, value: Value) -> Result<(), RuntimeError> {
         if self.values.borrow().contains_key(&name.lexeme) {
             self.values.borrow_mut().insert(name.lexeme.clone(), value);
             return Ok(());
         }
 
         if let Some(enclosing) = &self.enclosing {
-            return enclosing.borrow_mut().assign(name, value);
+            return enclosing.borrow().assign(name, value);
+        }
+        Err(RuntimeError::new(
+            name,
+            &format!("Undefined variable '{}'.", name.lexeme),
+        ))
+    }
+
+    /// Reads `name` from the environment exactly `distance` hops up the
+    /// enclosing chain, as computed by the resolver.
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Value, RuntimeError> {
+        if distance == 0 {
+            if let Some(value) = self.values.borrow().get(&name.lexeme).cloned() {
+                return Ok(value);
+            }
+        } else if let Some(enclosing) = &self.enclosing {
+            return enclosing.borrow().get_at(distance - 1, name);
+        }
+        Err(RuntimeError::new(
+            name,
+            &format!("Undefined variable '{}'.", name.lexeme),
+        ))
+    }
+
+    pub fn assign_at(
+        &self,
+        distance: usize,
+        name: &Token,
+        value: Value,
+    ) -> Result<(), RuntimeError> {
+        if distance == 0 {
+            self.values.borrow_mut().insert(name.lexeme.clone(), value);
+            return Ok(());
+        }
+        if let Some(enclosing) = &self.enclosing {
+            return enclosing.borrow().assign_at(distance - 1, name, value);
         }
-        Err(format!("Undefined variable '{}'.", name.lexeme))
+        Err(RuntimeError::new(
+            name,
+            &format!("Undefined variable '{}'.", name.lexeme),
+        ))
     }
 }
@@ -1,21 +1,47 @@
 use core::fmt;
 use std::fmt::Display;
 
+use super::{
+    runtime_error::RuntimeError,
+    token::{Span, Token},
+    token_type::TokenType,
+};
+
 #[derive(Debug, Clone)]
 pub struct LoxError {
-    line: usize,
-    at: Option<String>,
-    message: String,
+    pub span: Span,
+    /// Lexeme of the token the error was reported at, if any.
+    pub at: Option<String>,
+    pub message: String,
+    /// Text of the offending line, once attached with `with_source`.
+    source_line: Option<String>,
 }
 
 impl LoxError {
-    pub fn new(line: usize, at: Option<String>, message: &str) -> Self {
+    pub fn new(span: Span, at: Option<String>, message: &str) -> Self {
         LoxError {
-            line,
+            span,
             at,
             message: message.to_string(),
+            source_line: None,
         }
     }
+
+    /// An error reported at `token`.
+    pub fn at(token: &Token, message: &str) -> Self {
+        let at = if token.ttype == TokenType::Eof {
+            None
+        } else {
+            Some(token.lexeme.clone())
+        };
+        LoxError::new(token.span, at, message)
+    }
+
+    /// Attaches the offending line of `source` so the error renders with it.
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source_line = source_line(source, &self.span);
+        self
+    }
 }
 
 impl Display for LoxError {
@@ -23,21 +49,123 @@ impl Display for LoxError {
         write!(
             f,
             "{}",
-            gen_error(self.line, self.at.clone(), self.message.clone())
-        )
+            gen_error(&self.span, self.at.clone(), self.message.clone())
+        )?;
+        if let Some(source_line) = &self.source_line {
+            write_snippet(f, source_line, &self.span)?;
+        }
+        Ok(())
     }
 }
 
-fn gen_error(line: usize, at: Option<String>, message: String) -> String {
-    format!(
-        "[line {}] Error {}: {}",
+/// The line of `source` that `span` starts on.
+pub(super) fn source_line(source: &str, span: &Span) -> Option<String> {
+    source
+        .lines()
+        .nth(span.line.saturating_sub(1))
+        .map(str::to_string)
+}
+
+/// Writes `line` below an error message with carets under `span`.
+pub(super) fn write_snippet(f: &mut fmt::Formatter<'_>, line: &str, span: &Span) -> fmt::Result {
+    let gutter = " ".repeat(span.line.to_string().len());
+    write!(
+        f,
+        "\n {} | {}\n {} | {}",
+        span.line,
         line,
+        gutter,
+        underline(line, span)
+    )
+}
+
+fn gen_error(span: &Span, at: Option<String>, message: String) -> String {
+    format!(
+        "[line {}:{}] Error{}: {}",
+        span.line,
+        span.column,
         if let Some(at) = at {
-            format!("'{}'", at)
+            format!(" '{}'", at)
         } else {
             "".to_string()
         },
         message
     )
-    .into()
+}
+
+/// Carets under the part of `line` covered by `span`, indented to match it.
+fn underline(line: &str, span: &Span) -> String {
+    let mut indent = String::new();
+    let mut carets = 0;
+    let mut covered = 0;
+    for (column, c) in line.chars().enumerate() {
+        if column + 1 < span.column {
+            // Keep tabs so the carets line up however the terminal renders them.
+            indent.push(if c == '\t' { '\t' } else { ' ' });
+        } else if covered < span.length {
+            carets += 1;
+            covered += c.len_utf8();
+        } else {
+            break;
+        }
+    }
+    format!("{}{}", indent, "^".repeat(carets.max(1)))
+}
+
+/// Why `Interpreter::run` stopped: the source never made it past the
+/// scanner/parser, or it failed while executing.
+#[derive(Debug)]
+pub enum RunError {
+    Compile(Vec<LoxError>),
+    Runtime(RuntimeError),
+}
+
+impl RunError {
+    /// Attaches the offending source lines, given that `source` is the
+    /// source numbered `source_id`.
+    pub fn with_source(self, source: &str, source_id: usize) -> Self {
+        match self {
+            RunError::Compile(errors) => RunError::Compile(
+                errors
+                    .into_iter()
+                    .map(|error| error.with_source(source))
+                    .collect(),
+            ),
+            RunError::Runtime(error) => RunError::Runtime(error.with_source(source, source_id)),
+        }
+    }
+}
+
+impl std::error::Error for LoxError {}
+
+impl std::error::Error for RunError {}
+
+impl From<LoxError> for RunError {
+    fn from(error: LoxError) -> Self {
+        RunError::Compile(vec![error])
+    }
+}
+
+impl From<Vec<LoxError>> for RunError {
+    fn from(errors: Vec<LoxError>) -> Self {
+        RunError::Compile(errors)
+    }
+}
+
+impl From<RuntimeError> for RunError {
+    fn from(error: RuntimeError) -> Self {
+        RunError::Runtime(error)
+    }
+}
+
+impl Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::Compile(errors) => {
+                let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+                write!(f, "{}", messages.join("\n"))
+            }
+            RunError::Runtime(error) => write!(f, "{}", error),
+        }
+    }
 }
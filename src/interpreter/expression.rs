@@ -1,35 +1,84 @@
-use super::{token::Token, token_type::Literal};
+use std::cell::Cell;
+
+use super::{
+    token::{Span, Token},
+    token_type::Literal,
+};
+
+/// How many scopes out from the innermost one a variable reference resolves
+/// to. Filled in by the resolver; `None` means the variable is global.
+pub type Depth = Cell<Option<usize>>;
 
 #[derive(Debug, Clone)]
 pub enum Expr {
-    Assign(Token, Box<Expr>),
+    Assign(Token, Box<Expr>, Depth),
     Binary(Box<Expr>, Token, Box<Expr>),
-    Grouping(Box<Expr>),
-    Literal(Literal),
+    Call(Box<Expr>, Token, Vec<Expr>),
+    Get(Box<Expr>, Token),
+    Grouping(Box<Expr>, Span),
+    /// A string with `${...}` parts: literal text and expressions, in order.
+    Interpolation(Vec<Expr>),
+    Literal(Literal, Span),
+    Logical(Box<Expr>, Token, Box<Expr>),
+    Set(Box<Expr>, Token, Box<Expr>),
+    Super(Token, Token, Depth),
+    This(Token, Depth),
     Unary(Token, Box<Expr>),
-    Variable(Token),
-    None,
+    Variable(Token, Depth),
 }
 
 pub trait ExprVisitor {
     type Res;
-    fn visit_binary(&self, left: &Box<Expr>, operator: &Token, right: &Box<Expr>) -> Self::Res;
-    fn visit_grouping(&self, expr: &Box<Expr>) -> Self::Res;
+    fn visit_assign(&self, name: &Token, value: &Expr, depth: &Depth) -> Self::Res;
+    fn visit_binary(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::Res;
+    fn visit_call(&self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Self::Res;
+    fn visit_get(&self, object: &Expr, name: &Token) -> Self::Res;
+    fn visit_grouping(&self, expr: &Expr) -> Self::Res;
+    fn visit_interpolation(&self, parts: &[Expr]) -> Self::Res;
     fn visit_literal(&self, literal: &Literal) -> Self::Res;
-    // fn evaluate_assign(&self, token: &Token, expr: &Box<Expr>) -> Self::Res;
-    fn visit_unary(&self, operator: &Token, expr: &Box<Expr>) -> Self::Res;
-    fn visit_variable(&self, name: &Token) -> Self::Res;
+    fn visit_logical(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::Res;
+    fn visit_set(&self, object: &Expr, name: &Token, value: &Expr) -> Self::Res;
+    fn visit_super(&self, keyword: &Token, method: &Token, depth: &Depth) -> Self::Res;
+    fn visit_this(&self, keyword: &Token, depth: &Depth) -> Self::Res;
+    fn visit_unary(&self, operator: &Token, expr: &Expr) -> Self::Res;
+    fn visit_variable(&self, name: &Token, depth: &Depth) -> Self::Res;
 }
 
 impl Expr {
     pub fn accept<V: ExprVisitor>(&self, visitor: &V) -> V::Res {
         match self {
+            Expr::Assign(name, value, depth) => visitor.visit_assign(name, value, depth),
             Expr::Binary(left, operator, right) => visitor.visit_binary(left, operator, right),
-            Expr::Grouping(expr) => visitor.visit_grouping(expr),
-            Expr::Literal(literal) => visitor.visit_literal(literal),
+            Expr::Call(callee, paren, arguments) => visitor.visit_call(callee, paren, arguments),
+            Expr::Get(object, name) => visitor.visit_get(object, name),
+            Expr::Grouping(expr, _) => visitor.visit_grouping(expr),
+            Expr::Interpolation(parts) => visitor.visit_interpolation(parts),
+            Expr::Literal(literal, _) => visitor.visit_literal(literal),
+            Expr::Logical(left, operator, right) => visitor.visit_logical(left, operator, right),
+            Expr::Set(object, name, value) => visitor.visit_set(object, name, value),
+            Expr::Super(keyword, method, depth) => visitor.visit_super(keyword, method, depth),
+            Expr::This(keyword, depth) => visitor.visit_this(keyword, depth),
             Expr::Unary(operator, expr) => visitor.visit_unary(operator, expr),
-            Expr::Variable(name) => visitor.visit_variable(name),
-            _ => unimplemented!(),
+            Expr::Variable(name, depth) => visitor.visit_variable(name, depth),
+        }
+    }
+
+    /// The source text this expression was parsed from.
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Assign(name, value, _) => name.span.merge(value.span()),
+            Expr::Binary(left, _, right) | Expr::Logical(left, _, right) => {
+                left.span().merge(right.span())
+            }
+            Expr::Call(callee, paren, _) => callee.span().merge(paren.span),
+            Expr::Get(object, name) => object.span().merge(name.span),
+            Expr::Grouping(_, span) | Expr::Literal(_, span) => *span,
+            Expr::Interpolation(parts) => parts[0].span().merge(parts[parts.len() - 1].span()),
+            Expr::Set(object, _, value) => object.span().merge(value.span()),
+            Expr::Super(keyword, method, _) => keyword.span.merge(method.span),
+            Expr::This(keyword, _) => keyword.span,
+            Expr::Unary(operator, expr) => operator.span.merge(expr.span()),
+            Expr::Variable(name, _) => name.span,
         }
     }
 }
@@ -1,177 +1,1273 @@
+// Only tests print syntax trees, to check how the parser groups expressions.
+#[cfg(test)]
 mod ast_printer;
+mod callable;
+mod class;
+mod convert;
 mod environment;
 mod error;
 mod expression;
+mod natives;
 mod parser;
+mod resolver;
+mod runtime_error;
 mod scanner;
 mod statement;
 mod token;
 mod token_type;
+mod value;
 
-use expression::{Expr, ExprVisitor};
+use std::{
+    cell::{Cell, RefCell},
+    fmt::Display,
+    io::{self, BufRead, Write},
+    rc::Rc,
+};
+
+pub use callable::{LoxFunction, NativeFunction};
+pub use class::{LoxClass, LoxInstance};
+pub use convert::{ConversionError, FromLox, IntoLox, IntoLoxArgs, NativeFn};
+pub use error::{LoxError, RunError};
+pub use runtime_error::RuntimeError;
+pub use token::Span;
+pub use value::Value;
+
+use expression::{Depth, Expr, ExprVisitor};
 use parser::Parser;
+use resolver::Resolver;
 use scanner::Scanner;
 use token::Token;
 
-use crate::interpreter::ast_printer::AstPrinter;
-
 use self::{
+    callable::LoxCallable,
     environment::Environment,
-    error::LoxError,
-    statement::{Statement, StmtVisitor},
+    runtime_error::Unwind,
+    statement::{FunctionDecl, Statement, StmtVisitor},
     token_type::{Literal, TokenType},
 };
 
+/// Applies an arithmetic operator. Two integers use checked integer
+/// arithmetic; any other pair of numbers is promoted to floats.
 macro_rules! binary_num_operation {
-    ( $left:expr, $operator:tt, $right:expr, $( $variant:ident ),+ ) => {
+    ( $left:expr, $operator:tt, $checked:ident, $right:expr, $token:expr ) => {
         match ($left, $right) {
-            $(
-                (Literal::$variant(left), Literal::$variant(right)) => {
-                    Literal::$variant(left $operator right)
-                },
-            )+
-            _ => Literal::None,
+            (Value::Integer(left), Value::Integer(right)) => left
+                .$checked(right)
+                .map(Value::Integer)
+                .ok_or_else(|| RuntimeError::new($token, "Integer overflow.")),
+            (left, right) => match (as_float(&left), as_float(&right)) {
+                (Some(left), Some(right)) => Ok(Value::Float(left $operator right)),
+                _ => Err(RuntimeError::new($token, "Operands must be numbers.")),
+            },
         }
     };
 }
 
 macro_rules! binary_bool_operation {
-    ( $left:expr, $operator:tt, $right:expr, $( $variant:ident ),+ ) => {
+    ( $left:expr, $operator:tt, $right:expr, $token:expr ) => {
         match ($left, $right) {
-            $(
-                (Literal::$variant(left), Literal::$variant(right)) => {
-                    Literal::Bool(left $operator right)
-                },
-            )+
-            _ => Literal::None,
+            (Value::Integer(left), Value::Integer(right)) => Ok(Value::Bool(left $operator right)),
+            (left, right) => match (as_float(&left), as_float(&right)) {
+                (Some(left), Some(right)) => Ok(Value::Bool(left $operator right)),
+                _ => Err(RuntimeError::new($token, "Operands must be numbers.")),
+            },
         }
     };
 }
 
+/// Equality: the first list of variants compares by value, the second,
+/// holding heap objects, by identity.
 macro_rules! binary_compare {
-    ( $left:expr, ==, $right:expr, $( $variant:ident ),+ ) => {
+    ( $left:expr, ==, $right:expr, $( $variant:ident ),+ ; $( $object:ident ),+ ) => {
         match ($left, $right) {
             $(
-                (Literal::$variant(left), Literal::$variant(right)) => left == right,
+                (Value::$variant(left), Value::$variant(right)) => left == right,
+            )+
+            $(
+                (Value::$object(left), Value::$object(right)) => Rc::ptr_eq(&left, &right),
             )+
+            (Value::Integer(left), Value::Float(right))
+            | (Value::Float(right), Value::Integer(left)) => left as f64 == right,
+            (Value::Nil, Value::Nil) => true,
             _ => false,
         }
     };
 
-    ( $left:expr, !=, $right:expr, $( $variant:ident ),+ ) => {
-        !binary_compare!($left, ==, $right, $( $variant ),+)
+    ( $left:expr, !=, $right:expr, $( $variant:ident ),+ ; $( $object:ident ),+ ) => {
+        !binary_compare!($left, ==, $right, $( $variant ),+ ; $( $object ),+)
     };
 }
+/// How deeply calls may nest by default before the program is stopped with
+/// "Stack overflow.". See `Interpreter::with_max_call_depth`.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 10_000;
+
+/// Stack space each call needs left before it starts. With less, the call
+/// runs on a new stack segment of `STACK_SEGMENT` bytes instead, so deep
+/// recursion does not depend on how big the host thread's stack is.
+const STACK_RED_ZONE: usize = 256 * 1024;
+const STACK_SEGMENT: usize = 2 * 1024 * 1024;
+
 pub struct Interpreter {
-    environment: Environment,
+    globals: Rc<RefCell<Environment>>,
+    environment: RefCell<Rc<RefCell<Environment>>>,
+    /// Where `print` writes.
+    output: Rc<RefCell<dyn Write>>,
+    /// Where errors are reported by `report`.
+    diagnostics: Rc<RefCell<dyn Write>>,
+    /// Where `input()` and `read_line` read from.
+    input: Rc<RefCell<dyn BufRead>>,
+    /// Number of calls currently running.
+    call_depth: Cell<usize>,
+    max_call_depth: usize,
+    /// Number of sources run so far, used as `Span::source_id`.
+    sources: Cell<usize>,
+}
+
+/// Puts the enclosing environment back when a block's scope ends, even if the
+/// block is left early.
+struct ScopeGuard<'a> {
+    interpreter: &'a Interpreter,
+    previous: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Drop for ScopeGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            self.interpreter.environment.replace(previous);
+        }
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Interpreter {
-        Interpreter {
-            environment: Environment::new(None),
+        let globals = Rc::new(RefCell::new(Environment::new(None)));
+        let interpreter = Interpreter {
+            environment: RefCell::new(Rc::clone(&globals)),
+            globals,
+            output: Rc::new(RefCell::new(io::stdout())),
+            diagnostics: Rc::new(RefCell::new(io::stderr())),
+            input: Rc::new(RefCell::new(io::BufReader::new(io::stdin()))),
+            call_depth: Cell::new(0),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            sources: Cell::new(0),
+        };
+        natives::define_all(&interpreter);
+        interpreter
+    }
+
+    /// Sends program output to `output` instead of stdout. Keep a clone of
+    /// the `Rc` to read back what was written.
+    pub fn with_output(mut self, output: Rc<RefCell<dyn Write>>) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Sends error reports to `diagnostics` instead of stderr.
+    pub fn with_diagnostics(mut self, diagnostics: Rc<RefCell<dyn Write>>) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    /// Reads `input()` lines from `input` instead of stdin.
+    pub fn with_input(mut self, input: Rc<RefCell<dyn BufRead>>) -> Self {
+        self.input = input;
+        self
+    }
+
+    /// Stops programs whose calls nest more than `depth` deep with a "Stack
+    /// overflow." runtime error.
+    pub fn with_max_call_depth(mut self, depth: usize) -> Self {
+        self.max_call_depth = depth;
+        self
+    }
+
+    /// Writes `message` as one line to the diagnostics stream. Failing to
+    /// report is not itself reported.
+    pub fn report(&self, message: &dyn Display) {
+        let _ = writeln!(self.diagnostics.borrow_mut(), "{}", message);
+    }
+
+    /// Writes `value` as one line to the program output, as `print` does.
+    pub fn print(&self, value: &Value) -> io::Result<()> {
+        writeln!(self.output.borrow_mut(), "{}", value)
+    }
+
+    /// Whether `source` needs more lines before it can be run: it has unclosed
+    /// parentheses or braces, or an unterminated string, block comment or
+    /// string interpolation.
+    pub fn is_incomplete(source: &str) -> bool {
+        let scanner = Scanner::new(source.to_string());
+        let tokens = match scanner.scan() {
+            Ok(tokens) => tokens,
+            Err(_) => return scanner.is_unterminated(),
+        };
+        if scanner.is_unterminated() {
+            return true;
+        }
+        let depth = tokens.iter().fold(0i64, |depth, token| match token.ttype {
+            TokenType::LeftParen | TokenType::LeftBrace => depth + 1,
+            TokenType::RightParen | TokenType::RightBrace => depth - 1,
+            _ => depth,
+        });
+        depth > 0
+    }
+
+    /// Reads the next line of input without its line ending, or `None` at the
+    /// end of input.
+    pub fn read_line(&self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.input.borrow_mut().read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.strip_suffix('\n').unwrap_or(&line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        Ok(Some(line.to_string()))
+    }
+
+    /// Defines a global function named `name` whose body is Rust code. The
+    /// body is only called with exactly `arity` arguments.
+    pub fn define_native<F>(&self, name: &str, arity: usize, body: F)
+    where
+        F: Fn(&Interpreter, &[Value]) -> Result<Value, RuntimeError> + 'static,
+    {
+        let function = NativeFunction::new(name, arity, body);
+        self.globals
+            .borrow()
+            .define(name.to_string(), Value::NativeFunction(Rc::new(function)));
+    }
+
+    /// Defines a global function named `name` from a closure over ordinary
+    /// Rust types, e.g. `|a: i64, b: i64| Ok(a + b)`. Arguments that do not
+    /// convert to the closure's parameter types are runtime errors.
+    pub fn define_fn<Args, F: NativeFn<Args>>(&self, name: &str, body: F) {
+        self.define_native(name, F::ARITY, move |_, arguments| {
+            body.call_with(arguments)
+        });
+    }
+    pub fn run(&self, source: String) -> Result<(), RunError> {
+        self.run_source(&source, false)?;
+        Ok(())
+    }
+
+    /// Runs `source` like `run`, and returns the value of its last statement
+    /// if that is an expression statement, or nil otherwise.
+    pub fn eval(&self, source: &str) -> Result<Value, RunError> {
+        self.run_source(source, true)
+    }
+
+    /// The value of the global variable `name`, if it is defined.
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.borrow().get_local(name)
+    }
+
+    /// Defines or overwrites the global variable `name`.
+    pub fn set_global(&self, name: &str, value: Value) {
+        self.globals.borrow().define(name.to_string(), value);
+    }
+
+    /// Calls the function or class stored in the global variable `name`.
+    pub fn call_global(&self, name: &str, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        let callee = self
+            .get_global(name)
+            .ok_or_else(|| RuntimeError::native(&format!("Undefined variable '{}'.", name)))?;
+        self.call(&callee, arguments)
+    }
+
+    /// Calls `callee` with `arguments`, checking that it is callable and takes
+    /// that many arguments.
+    fn call(&self, callee: &Value, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        let function: &dyn LoxCallable = match callee {
+            Value::Function(function) => function.as_ref(),
+            Value::NativeFunction(function) => function.as_ref(),
+            Value::Class(class) => class,
+            _ => return Err(RuntimeError::native("Can only call functions and classes.")),
+        };
+        if arguments.len() != function.arity() {
+            return Err(RuntimeError::native(&format!(
+                "Expected {} arguments but got {}.",
+                function.arity(),
+                arguments.len()
+            )));
+        }
+        let depth = self.call_depth.get();
+        if depth >= self.max_call_depth {
+            return Err(RuntimeError::native("Stack overflow."));
         }
+        self.call_depth.set(depth + 1);
+        let result = stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT, || {
+            function.call(self, arguments)
+        });
+        self.call_depth.set(depth);
+        result
+    }
+
+    /// Compiles and runs `source` under a new source id, so that errors
+    /// pointing into it are shown with the offending line.
+    fn run_source(&self, source: &str, trailing_expression: bool) -> Result<Value, RunError> {
+        let source_id = self.sources.get() + 1;
+        self.sources.set(source_id);
+        let result = self
+            .compile(source, source_id, trailing_expression)
+            .and_then(|statements| Ok(self.interpret_value(&statements)?));
+        result.map_err(|error| error.with_source(source, source_id))
     }
-    pub fn run(&self, source: String) -> Result<(), LoxError> {
-        let scanner = Scanner::new(source);
+
+    /// Scans, parses and resolves `source` without running it.
+    /// With `trailing_expression`, a final expression statement may leave
+    /// out its `;`.
+    fn compile(
+        &self,
+        source: &str,
+        source_id: usize,
+        trailing_expression: bool,
+    ) -> Result<Vec<Statement>, RunError> {
+        let scanner = Scanner::new(source.to_string()).with_source_id(source_id);
         let tokens = scanner.scan()?;
 
-        let parser = Parser::new(tokens);
+        let mut parser = Parser::new(tokens);
+        if trailing_expression {
+            parser = parser.with_trailing_expression();
+        }
         let statements = parser.parse()?;
-        self.interpret(&statements);
 
-        Ok(())
+        let resolver = Resolver::new();
+        resolver.resolve(&statements)?;
+
+        Ok(statements)
     }
 
-    pub fn interpret(&self, statements: &Vec<Statement>) {
+    /// Runs `statements` and returns the value of the last one if it is an
+    /// expression statement, or nil otherwise.
+    fn interpret_value(&self, statements: &[Statement]) -> Result<Value, RuntimeError> {
+        match statements.split_last() {
+            Some((Statement::Expression(expr, span), rest)) => {
+                self.interpret(rest)?;
+                self.evaluate(expr)
+                    .map_err(|error| error.with_default_span(*span))
+            }
+            _ => {
+                self.interpret(statements)?;
+                Ok(Value::Nil)
+            }
+        }
+    }
+
+    fn interpret(&self, statements: &[Statement]) -> Result<(), RuntimeError> {
         for stmt in statements {
-            self.execute(stmt);
+            match self.execute(stmt) {
+                Ok(()) => {}
+                // A top-level `return` simply ends the script.
+                Err(Unwind::Return(_)) => break,
+                Err(Unwind::Error(error)) => return Err(error),
+            }
         }
+        Ok(())
+    }
+    fn execute(&self, stmt: &Statement) -> Result<(), Unwind> {
+        stmt.accept(self).map_err(|unwind| match unwind {
+            Unwind::Error(error) => Unwind::Error(error.with_default_span(stmt.span())),
+            unwind => unwind,
+        })
     }
-    fn execute(&self, stmt: &Statement) {
-        stmt.accept(self);
+    fn execute_block(
+        &self,
+        statements: &[Statement],
+        environment: Environment,
+    ) -> Result<(), Unwind> {
+        let _guard = ScopeGuard {
+            interpreter: self,
+            previous: Some(self.environment.replace(Rc::new(RefCell::new(environment)))),
+        };
+        for stmt in statements {
+            self.execute(stmt)?;
+        }
+        Ok(())
     }
-    fn evaluate(&self, expr: &Box<Expr>) -> Literal {
+    fn evaluate(&self, expr: &Expr) -> Result<Value, RuntimeError> {
         expr.accept(self)
     }
+    fn look_up_variable(&self, name: &Token, depth: &Depth) -> Result<Value, RuntimeError> {
+        match depth.get() {
+            Some(distance) => self.environment.borrow().borrow().get_at(distance, name),
+            None => self.globals.borrow().get(name),
+        }
+    }
 }
 
-impl ExprVisitor for Interpreter {
-    type Res = Literal;
-    fn visit_binary(&self, left: &Box<Expr>, operator: &Token, right: &Box<Expr>) -> Self::Res {
-        let left = self.evaluate(left);
-        let right = self.evaluate(right);
-
-        match operator.ttype {
-            TokenType::Minus => binary_num_operation!(left, -, right, Float, Integer),
-            TokenType::Slash => binary_num_operation!(left, /, right, Float, Integer),
-            TokenType::Star => binary_num_operation!(left, *, right, Float, Integer),
-            TokenType::Plus => match (left, right) {
-                (Literal::Float(left), Literal::Float(right)) => Literal::Float(left + right),
-                (Literal::Integer(left), Literal::Integer(right)) => Literal::Integer(left + right),
-                (Literal::String(left), Literal::String(right)) => Literal::String(left + &right),
-                _ => Literal::None,
-            },
-            TokenType::Greater => binary_bool_operation!(left, >, right, Float, Integer),
-            TokenType::GreaterEqual => binary_bool_operation!(left, >=, right, Float, Integer),
-            TokenType::Less => binary_bool_operation!(left, <, right, Float, Integer),
-            TokenType::LessEqual => binary_bool_operation!(left, <=, right, Float, Integer),
-            TokenType::BangEqual => {
-                Literal::Bool(binary_compare!(left, !=, right, Float, Integer, String, Bool))
+/// The value of a number as a float, or `None` for non-numbers.
+fn as_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(val) => Some(*val as f64),
+        Value::Float(val) => Some(*val),
+        _ => None,
+    }
+}
+
+/// Floored modulo: the result takes the sign of the divisor, matching `~/`
+/// so that `a == (a ~/ b) * b + a % b`. The divisor must be non-zero.
+fn modulo(left: Value, right: Value, operator: &Token) -> Result<Value, RuntimeError> {
+    match (left, right) {
+        (Value::Integer(left), Value::Integer(right)) => {
+            let remainder = left.wrapping_rem(right);
+            if remainder != 0 && (remainder < 0) != (right < 0) {
+                Ok(Value::Integer(remainder + right))
+            } else {
+                Ok(Value::Integer(remainder))
+            }
+        }
+        (left, right) => match (as_float(&left), as_float(&right)) {
+            (Some(left), Some(right)) => {
+                let remainder = left % right;
+                if remainder != 0.0 && (remainder < 0.0) != (right < 0.0) {
+                    Ok(Value::Float(remainder + right))
+                } else {
+                    Ok(Value::Float(remainder))
+                }
+            }
+            _ => Err(RuntimeError::new(operator, "Operands must be numbers.")),
+        },
+    }
+}
+
+/// Division rounding toward negative infinity. The divisor must be non-zero.
+fn floor_divide(left: Value, right: Value, operator: &Token) -> Result<Value, RuntimeError> {
+    match (left, right) {
+        (Value::Integer(left), Value::Integer(right)) => {
+            let quotient = left
+                .checked_div(right)
+                .ok_or_else(|| RuntimeError::new(operator, "Integer overflow."))?;
+            if left % right != 0 && (left < 0) != (right < 0) {
+                Ok(Value::Integer(quotient - 1))
+            } else {
+                Ok(Value::Integer(quotient))
+            }
+        }
+        (left, right) => match (as_float(&left), as_float(&right)) {
+            (Some(left), Some(right)) => Ok(Value::Float((left / right).floor())),
+            _ => Err(RuntimeError::new(operator, "Operands must be numbers.")),
+        },
+    }
+}
+
+/// Exponentiation. An integer raised to a non-negative integer stays an
+/// integer; anything else, including a negative exponent, gives a float.
+fn power(left: Value, right: Value, operator: &Token) -> Result<Value, RuntimeError> {
+    match (left, right) {
+        (Value::Integer(base), Value::Integer(exponent)) if exponent >= 0 => {
+            let result = match base {
+                // Never overflow, however large the exponent.
+                0 | 1 => Some(if exponent == 0 { 1 } else { base }),
+                -1 => Some(if exponent % 2 == 0 { 1 } else { -1 }),
+                // Any other base overflows long before `u32::MAX`.
+                _ => u32::try_from(exponent)
+                    .ok()
+                    .and_then(|exponent| base.checked_pow(exponent)),
+            };
+            result
+                .map(Value::Integer)
+                .ok_or_else(|| RuntimeError::new(operator, "Integer overflow."))
+        }
+        (left, right) => match (as_float(&left), as_float(&right)) {
+            (Some(base), Some(exponent)) => Ok(Value::Float(base.powf(exponent))),
+            _ => Err(RuntimeError::new(operator, "Operands must be numbers.")),
+        },
+    }
+}
+
+/// Bitwise and shift operators, defined only on integers. `>>` is an
+/// arithmetic shift, so it keeps the sign.
+fn bitwise(left: Value, right: Value, operator: &Token) -> Result<Value, RuntimeError> {
+    let (Value::Integer(left), Value::Integer(right)) = (left, right) else {
+        return Err(RuntimeError::new(operator, "Operands must be integers."));
+    };
+    let result = match operator.ttype {
+        TokenType::Ampersand => left & right,
+        TokenType::Pipe => left | right,
+        TokenType::Caret => left ^ right,
+        _ => {
+            let shift = u32::try_from(right)
+                .ok()
+                .filter(|shift| *shift < i64::BITS)
+                .ok_or_else(|| {
+                    RuntimeError::new(operator, "Shift amount must be between 0 and 63.")
+                })?;
+            if operator.ttype == TokenType::LessLess {
+                left << shift
+            } else {
+                left >> shift
+            }
+        }
+    };
+    Ok(Value::Integer(result))
+}
+
+/// Applies a binary operator to its evaluated operands. Kept out of
+/// `visit_binary` so its locals don't sit on the stack of every nested call.
+fn binary_operation(left: Value, operator: &Token, right: Value) -> Result<Value, RuntimeError> {
+    match operator.ttype {
+        TokenType::Minus => binary_num_operation!(left, -, checked_sub, right, operator),
+        TokenType::Slash | TokenType::Percent | TokenType::TildeSlash
+            if as_float(&left).is_some() && as_float(&right) == Some(0.0) =>
+        {
+            Err(RuntimeError::new(operator, "Division by zero."))
+        }
+        TokenType::Slash => binary_num_operation!(left, /, checked_div, right, operator),
+        TokenType::Star => binary_num_operation!(left, *, checked_mul, right, operator),
+        TokenType::Percent => modulo(left, right, operator),
+        TokenType::TildeSlash => floor_divide(left, right, operator),
+        TokenType::StarStar => power(left, right, operator),
+        TokenType::Ampersand
+        | TokenType::Pipe
+        | TokenType::Caret
+        | TokenType::LessLess
+        | TokenType::GreaterGreater => bitwise(left, right, operator),
+        TokenType::Plus => match (left, right) {
+            (Value::String(left), Value::String(right)) => Ok(Value::String(left + &right)),
+            (left, right) if as_float(&left).is_some() && as_float(&right).is_some() => {
+                binary_num_operation!(left, +, checked_add, right, operator)
             }
-            TokenType::EqualEqual => {
-                Literal::Bool(binary_compare!(left, ==, right, Float, Integer, String, Bool))
+            _ => Err(RuntimeError::new(
+                operator,
+                "Operands must be two numbers or two strings.",
+            )),
+        },
+        TokenType::Greater => binary_bool_operation!(left, >, right, operator),
+        TokenType::GreaterEqual => {
+            binary_bool_operation!(left, >=, right, operator)
+        }
+        TokenType::Less => binary_bool_operation!(left, <, right, operator),
+        TokenType::LessEqual => {
+            binary_bool_operation!(left, <=, right, operator)
+        }
+        TokenType::BangEqual => Ok(Value::Bool(binary_compare!(
+            left, !=, right, Float, Integer, String, Bool;
+            Function, NativeFunction, Class, Instance, List, Map
+        ))),
+        TokenType::EqualEqual => Ok(Value::Bool(binary_compare!(
+            left, ==, right, Float, Integer, String, Bool;
+            Function, NativeFunction, Class, Instance, List, Map
+        ))),
+        _ => Err(RuntimeError::new(operator, "Unknown binary operator.")),
+    }
+}
+
+/// Lox truthiness: `nil` and `false` are falsey, everything else is truthy.
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Nil => false,
+        Value::Bool(val) => *val,
+        _ => true,
+    }
+}
+
+impl ExprVisitor for Interpreter {
+    type Res = Result<Value, RuntimeError>;
+    fn visit_assign(&self, name: &Token, value: &Expr, depth: &Depth) -> Self::Res {
+        let value = self.evaluate(value)?;
+        match depth.get() {
+            Some(distance) => {
+                self.environment
+                    .borrow()
+                    .borrow()
+                    .assign_at(distance, name, value.clone())?
             }
-            _ => Literal::None,
+            None => self.globals.borrow().assign(name, value.clone())?,
+        }
+        Ok(value)
+    }
+
+    fn visit_binary(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::Res {
+        let span = left.span().merge(right.span());
+        let left = self.evaluate(left)?;
+        let right = self.evaluate(right)?;
+
+        let result = binary_operation(left, operator, right);
+        result.map_err(|error| error.with_span(span))
+    }
+
+    fn visit_call(&self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Self::Res {
+        let span = callee.span().merge(paren.span);
+        let callee = self.evaluate(callee)?;
+        let arguments = arguments
+            .iter()
+            .map(|argument| self.evaluate(argument))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.call(&callee, arguments)
+            .map_err(|error| error.with_default_span(span))
+    }
+
+    fn visit_get(&self, object: &Expr, name: &Token) -> Self::Res {
+        match self.evaluate(object)? {
+            Value::Instance(instance) => instance.get(name),
+            _ => Err(RuntimeError::new(name, "Only instances have properties.")),
         }
     }
 
-    fn visit_grouping(&self, expr: &Box<Expr>) -> Self::Res {
+    fn visit_grouping(&self, expr: &Expr) -> Self::Res {
         self.evaluate(expr)
     }
 
+    fn visit_interpolation(&self, parts: &[Expr]) -> Self::Res {
+        let mut string = String::new();
+        for part in parts {
+            string.push_str(&self.evaluate(part)?.to_string());
+        }
+        Ok(Value::String(string))
+    }
+
     fn visit_literal(&self, literal: &Literal) -> Self::Res {
-        literal.clone()
-    }
-
-    fn visit_unary(&self, operator: &Token, expr: &Box<Expr>) -> Self::Res {
-        let right = self.evaluate(expr);
-        match operator.ttype {
-            TokenType::Bang => match right {
-                Literal::Bool(val) => Literal::Bool(!val),
-                Literal::Float(_) => Literal::Bool(false),
-                Literal::Integer(val) => Literal::Bool(if val == 0 { true } else { false }),
-                Literal::String(val) => Literal::Bool(if val.is_empty() { true } else { false }),
-                _ => Literal::Bool(false),
-            },
+        Ok(Value::from(literal))
+    }
+
+    fn visit_logical(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::Res {
+        let left = self.evaluate(left)?;
+
+        // Short-circuit on the left operand, yielding whichever operand decided
+        // the result rather than a plain bool.
+        if operator.ttype == TokenType::Or {
+            if is_truthy(&left) {
+                return Ok(left);
+            }
+        } else if !is_truthy(&left) {
+            return Ok(left);
+        }
+        self.evaluate(right)
+    }
+
+    fn visit_set(&self, object: &Expr, name: &Token, value: &Expr) -> Self::Res {
+        let Value::Instance(instance) = self.evaluate(object)? else {
+            return Err(RuntimeError::new(name, "Only instances have fields."));
+        };
+        let value = self.evaluate(value)?;
+        instance.set(name, value.clone());
+        Ok(value)
+    }
+
+    fn visit_super(&self, keyword: &Token, method: &Token, depth: &Depth) -> Self::Res {
+        let Some(distance) = depth.get() else {
+            return Err(RuntimeError::new(keyword, "Can't use 'super' here."));
+        };
+        let environment = Rc::clone(&self.environment.borrow());
+        let superclass = environment.borrow().get_at(distance, keyword)?;
+        // `this` lives in the scope just inside the one that binds `super`.
+        let this = Token::new(TokenType::This, "this".into(), Literal::None, keyword.span);
+        let object = environment.borrow().get_at(distance - 1, &this)?;
+
+        let (Value::Class(superclass), Value::Instance(object)) = (superclass, object) else {
+            return Err(RuntimeError::new(keyword, "Can't use 'super' here."));
+        };
+        match superclass.find_method(&method.lexeme) {
+            Some(found) => Ok(Value::Function(Rc::new(found.bind(object)))),
+            None => Err(RuntimeError::new(
+                method,
+                &format!("Undefined property '{}'.", method.lexeme),
+            )),
+        }
+    }
+
+    fn visit_this(&self, keyword: &Token, depth: &Depth) -> Self::Res {
+        self.look_up_variable(keyword, depth)
+    }
+
+    fn visit_unary(&self, operator: &Token, expr: &Expr) -> Self::Res {
+        let span = operator.span.merge(expr.span());
+        let right = self.evaluate(expr)?;
+        let result = match operator.ttype {
+            TokenType::Bang => Ok(Value::Bool(!is_truthy(&right))),
             TokenType::Minus => match right {
-                Literal::Float(val) => Literal::Float(-val),
-                Literal::Integer(val) => Literal::Integer(-val),
-                _ => Literal::None,
+                Value::Float(val) => Ok(Value::Float(-val)),
+                Value::Integer(val) => val
+                    .checked_neg()
+                    .map(Value::Integer)
+                    .ok_or_else(|| RuntimeError::new(operator, "Integer overflow.")),
+                _ => Err(RuntimeError::new(operator, "Operand must be a number.")),
             },
-            _ => Literal::None,
-        }
+            TokenType::Tilde => match right {
+                Value::Integer(val) => Ok(Value::Integer(!val)),
+                _ => Err(RuntimeError::new(operator, "Operand must be an integer.")),
+            },
+            _ => Err(RuntimeError::new(operator, "Unknown unary operator.")),
+        };
+        result.map_err(|error| error.with_span(span))
     }
-    fn visit_variable(&self, name: &Token) -> Self::Res {
-        self.environment.get(name).unwrap()
+    fn visit_variable(&self, name: &Token, depth: &Depth) -> Self::Res {
+        self.look_up_variable(name, depth)
     }
 }
 
 impl StmtVisitor for Interpreter {
-    type Res = ();
-    fn visit_expression_stmt(&self, expr: &Box<Expr>) -> Self::Res {
-        self.evaluate(expr);
+    type Res = Result<(), Unwind>;
+    fn visit_block_stmt(&self, statements: &[Statement]) -> Self::Res {
+        let enclosing = Rc::clone(&self.environment.borrow());
+        self.execute_block(statements, Environment::new(Some(enclosing)))
+    }
+    fn visit_class_stmt(
+        &self,
+        name: &Token,
+        superclass: &Option<Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> Self::Res {
+        let superclass = match superclass {
+            Some(expr) => match (self.evaluate(expr)?, expr) {
+                (Value::Class(class), _) => Some(class),
+                (_, Expr::Variable(superclass_name, _)) => {
+                    return Err(
+                        RuntimeError::new(superclass_name, "Superclass must be a class.").into(),
+                    )
+                }
+                _ => return Err(RuntimeError::new(name, "Superclass must be a class.").into()),
+            },
+            None => None,
+        };
+
+        let environment = Rc::clone(&self.environment.borrow());
+        environment.borrow().define(name.lexeme.clone(), Value::Nil);
+
+        // Methods of a subclass close over an extra scope that binds `super`.
+        let method_closure = match &superclass {
+            Some(superclass) => {
+                let scope = Environment::new(Some(Rc::clone(&environment)));
+                scope.define("super".to_string(), Value::Class(Rc::clone(superclass)));
+                Rc::new(RefCell::new(scope))
+            }
+            None => Rc::clone(&environment),
+        };
+
+        let methods = methods
+            .iter()
+            .map(|method| {
+                let function = LoxFunction::new(
+                    Rc::clone(method),
+                    Rc::clone(&method_closure),
+                    method.name.lexeme == "init",
+                );
+                (method.name.lexeme.clone(), Rc::new(function))
+            })
+            .collect();
+
+        let class = LoxClass::new(name.lexeme.clone(), superclass, methods);
+        environment
+            .borrow()
+            .assign(name, Value::Class(Rc::new(class)))?;
+        Ok(())
+    }
+
+    fn visit_expression_stmt(&self, expr: &Expr) -> Self::Res {
+        self.evaluate(expr)?;
+        Ok(())
+    }
+    fn visit_print_stmt(&self, expr: &Expr) -> Self::Res {
+        let value = self.evaluate(expr)?;
+        self.print(&value)
+            .map_err(|e| RuntimeError::native(&format!("Failed to write output. {}", e)))?;
+        Ok(())
+    }
+
+    fn visit_function_stmt(&self, declaration: &Rc<FunctionDecl>) -> Self::Res {
+        let closure = Rc::clone(&self.environment.borrow());
+        let function = LoxFunction::new(Rc::clone(declaration), closure, false);
+        self.environment.borrow().borrow().define(
+            declaration.name.lexeme.clone(),
+            Value::Function(Rc::new(function)),
+        );
+        Ok(())
+    }
+
+    fn visit_if_stmt(
+        &self,
+        condition: &Expr,
+        then_branch: &Statement,
+        else_branch: &Option<Box<Statement>>,
+    ) -> Self::Res {
+        if is_truthy(&self.evaluate(condition)?) {
+            self.execute(then_branch)
+        } else if let Some(else_branch) = else_branch {
+            self.execute(else_branch)
+        } else {
+            Ok(())
+        }
     }
-    fn visit_print_stmt(&self, expr: &Box<Expr>) -> Self::Res {
-        let value = self.evaluate(expr);
-        println!("{:?}", value);
+
+    fn visit_return_stmt(&self, _keyword: &Token, value: &Option<Expr>) -> Self::Res {
+        let value = match value {
+            Some(value) => self.evaluate(value)?,
+            None => Value::Nil,
+        };
+        Err(Unwind::Return(value))
     }
 
     fn visit_var_stmt(&self, name: &Token, initializer: &Option<Expr>) -> Self::Res {
-        let mut value = Literal::None;
+        let mut value = Value::Nil;
         if let Some(initial) = initializer {
-            value = self.evaluate(&Box::new(initial.clone()));
+            value = self.evaluate(initial)?;
+        }
+        self.environment
+            .borrow()
+            .borrow()
+            .define(name.lexeme.clone(), value);
+        Ok(())
+    }
+
+    fn visit_while_stmt(&self, condition: &Expr, body: &Statement) -> Self::Res {
+        while is_truthy(&self.evaluate(condition)?) {
+            self.execute(body)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// An interpreter whose `print` output is collected in the returned buffer.
+    fn capturing() -> (Interpreter, Rc<RefCell<Vec<u8>>>) {
+        let printed = Rc::new(RefCell::new(Vec::new()));
+        (Interpreter::new().with_output(printed.clone()), printed)
+    }
+
+    fn run_captured(interpreter: &Interpreter, printed: &RefCell<Vec<u8>>, source: &str) -> String {
+        if let Err(error) = interpreter.run(source.to_string()) {
+            panic!("{}", error);
         }
-        self.environment.define(name.lexeme.clone(), value);
+        String::from_utf8(printed.take()).unwrap()
+    }
+
+    /// Runs `source` and returns everything it printed.
+    fn output(source: &str) -> String {
+        let (interpreter, printed) = capturing();
+        run_captured(&interpreter, &printed, source)
+    }
+
+    #[test]
+    fn objects_are_equal_only_to_themselves() {
+        let source = "
+            class A { init(n) {} }
+            var a = A(1);
+            var b = A(1);
+            print a == a; print a == b; print a != b;
+            print A == A; print a.init(9) == a;
+            fun f() {}
+            fun g() {}
+            print f == f; print f == g;
+        ";
+        assert_eq!(
+            output(source),
+            "true\nfalse\ntrue\ntrue\ntrue\ntrue\nfalse\n"
+        );
+        assert_eq!(
+            output("print clock == clock; print clock == len;"),
+            "true\nfalse\n"
+        );
+    }
+
+    #[test]
+    fn host_lists_and_maps_are_equal_only_to_themselves() {
+        let (interpreter, printed) = capturing();
+        interpreter.set_global("xs", vec![1i64].into_lox());
+        interpreter.set_global("ys", vec![1i64].into_lox());
+        let map = HashMap::from([("a".to_string(), 1i64)]);
+        interpreter.set_global("m", map.clone().into_lox());
+        interpreter.set_global("n", map.into_lox());
+        let source = "print xs == xs; print xs == ys; print m == m; print m != n;";
+        assert_eq!(
+            run_captured(&interpreter, &printed, source),
+            "true\nfalse\ntrue\ntrue\n"
+        );
+    }
+
+    /// Runs `source`, expecting it to fail at runtime, and returns the error.
+    fn runtime_error(source: &str) -> RuntimeError {
+        match Interpreter::new().run(source.to_string()) {
+            Err(RunError::Runtime(error)) => error,
+            other => panic!("expected a runtime error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn runaway_recursion_is_a_runtime_error() {
+        let error = runtime_error("fun f(n) { return f(n + 1); }\nf(0);");
+        assert_eq!(error.message, "Stack overflow.");
+        // Calls unwind normally afterwards.
+        let source = "
+            fun f(n) { return f(n + 1); }
+            fun depth(n) { if (n == 0) return 0; return depth(n - 1) + 1; }
+            print depth(200);
+        ";
+        assert_eq!(output(source), "200\n");
+        let error = Interpreter::new()
+            .with_max_call_depth(10)
+            .run("fun f(n) { if (n > 0) f(n - 1); }\nf(10);".to_string())
+            .unwrap_err();
+        assert!(error.to_string().contains("Stack overflow."), "{}", error);
+    }
+
+    #[test]
+    fn deep_recursion_does_not_depend_on_the_thread_stack() {
+        let source = "
+            class Node {
+                init(n) {
+                    this.n = n;
+                    if (n > 0) this.next = Node(n - 1); else this.next = nil;
+                }
+                length() {
+                    if (this.next == nil) return 1;
+                    return 1 + this.next.length();
+                }
+            }
+            fun f(n) { if (n == 0) return 0; return 1 + f(n - 1); }
+            print Node(2000).length();
+            print f(5000);
+        ";
+        // The default stack size of spawned threads and of `cargo test`.
+        let thread = std::thread::Builder::new().stack_size(2 * 1024 * 1024);
+        let printed = thread
+            .spawn(move || output(source))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(printed, "2001\n5000\n");
+    }
+
+    #[test]
+    fn nil_is_only_equal_to_nil() {
+        assert_eq!(
+            output("print nil == nil; print nil == false;"),
+            "true\nfalse\n"
+        );
+    }
+
+    #[test]
+    fn runtime_errors_show_the_offending_line() {
+        let error = Interpreter::new()
+            .run("var a = 1;\nprint a + nil;".to_string())
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 2:7] Runtime error: Operands must be two numbers or two strings.\n \
+             2 | print a + nil;\n   |       ^^^^^^^"
+        );
+    }
+
+    #[test]
+    fn errors_in_earlier_sources_have_no_line() {
+        let interpreter = Interpreter::new();
+        interpreter
+            .run("fun f() { return -nil; }".to_string())
+            .unwrap();
+        let error = interpreter.run("f();".to_string()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 1:18] Runtime error: Operand must be a number."
+        );
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("closed"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn errors_without_a_location_point_at_their_statement() {
+        let interpreter = Interpreter::new().with_output(Rc::new(RefCell::new(FailingWriter)));
+        let error = interpreter
+            .run("var a;\n  print 1;".to_string())
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 2:3] Runtime error: Failed to write output. closed\n \
+             2 |   print 1;\n   |   ^^^^^^^^"
+        );
+    }
+
+    /// Runs `source`, expecting it not to compile, and returns the messages.
+    fn compile_errors(source: &str) -> Vec<String> {
+        match Interpreter::new().run(source.to_string()) {
+            Err(RunError::Compile(errors)) => errors.into_iter().map(|e| e.message).collect(),
+            other => panic!("expected compile errors, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn resolver_reports_every_error() {
+        let source = "return 1;\nclass X < X {}\nfun f() { print this; }";
+        assert_eq!(
+            compile_errors(source),
+            [
+                "Can't return from top-level code.",
+                "A class can't inherit from itself.",
+                "Can't use 'this' outside of a class.",
+            ]
+        );
+    }
+
+    #[test]
+    fn closures_bind_the_variable_in_scope_when_declared() {
+        let source = "
+            var a = \"global\";
+            {
+                fun show() { print a; }
+                show();
+                var a = \"block\";
+                show();
+            }
+        ";
+        assert_eq!(output(source), "global\nglobal\n");
+    }
+
+    #[test]
+    fn resolver_rejects_misused_locals() {
+        assert_eq!(
+            compile_errors("{ var a = 1; var a = 2; }"),
+            ["Already a variable with this name in this scope."]
+        );
+        assert_eq!(
+            compile_errors("var a = 1; { var a = a; }"),
+            ["Can't read local variable in its own initializer."]
+        );
+        // Globals may be redeclared.
+        assert_eq!(output("var a = 1; var a = a + 1; print a;"), "2\n");
+    }
+
+    #[test]
+    fn mixed_arithmetic_promotes_to_float() {
+        assert_eq!(
+            output("print 1 + 2.5; print 3 * 0.5; print 7 / 2; print 1 == 1.0; print 2.0 != 2;"),
+            "3.5\n1.5\n3\ntrue\nfalse\n"
+        );
+    }
+
+    #[test]
+    fn integer_overflow_and_division_by_zero_are_runtime_errors() {
+        let error = runtime_error("print 9223372036854775807 + 1;");
+        assert_eq!(error.message, "Integer overflow.");
+        let error = runtime_error("var min = -9223372036854775807 - 1; print min * -1;");
+        assert_eq!(error.message, "Integer overflow.");
+        assert_eq!(runtime_error("print 1 / 0;").message, "Division by zero.");
+        assert_eq!(runtime_error("print 1 / 0.0;").message, "Division by zero.");
+    }
+
+    #[test]
+    fn modulo_and_floor_division_round_toward_negative_infinity() {
+        assert_eq!(
+            output("print -7 % 3; print 7 % -3; print -7 ~/ 2; print 7 ~/ 2; print -7.5 ~/ 2;"),
+            "2\n-2\n-4\n3\n-4\n"
+        );
+        let error = runtime_error("var min = -9223372036854775807 - 1; print min ~/ -1;");
+        assert_eq!(error.message, "Integer overflow.");
+    }
+
+    #[test]
+    fn modulo_and_floor_division_by_zero_are_runtime_errors() {
+        for source in [
+            "print 1 % 0;",
+            "print 1 % 0.0;",
+            "print 1 ~/ 0;",
+            "print 1.0 ~/ 0.0;",
+        ] {
+            assert_eq!(runtime_error(source).message, "Division by zero.");
+        }
+    }
+
+    #[test]
+    fn exponent_is_right_associative_and_binds_tighter_than_unary() {
+        assert_eq!(
+            output("print 2 ** 3 ** 2; print -2 ** 2; print 2 ** -1;"),
+            "512\n-4\n0.5\n"
+        );
+    }
+
+    #[test]
+    fn huge_exponents_overflow_only_when_the_result_does() {
+        let source = "
+            var big = 5000000000;
+            print 1 ** big; print 0 ** big; print 0 ** 0;
+            print (-1) ** big; print (-1) ** (big + 1);
+            print 2 ** 62;
+        ";
+        assert_eq!(output(source), "1\n0\n1\n1\n-1\n4611686018427387904\n");
+        for source in [
+            "print 2 ** 63;",
+            "print 2 ** 5000000000;",
+            "print (-3) ** 5000000001;",
+        ] {
+            assert_eq!(runtime_error(source).message, "Integer overflow.");
+        }
+    }
+
+    #[test]
+    fn bitwise_operators_work_on_integers() {
+        assert_eq!(
+            output(
+                "print 6 & 3; print 6 | 3; print 6 ^ 3; print ~5; print 1 << 4; print -16 >> 2;"
+            ),
+            "2\n7\n5\n-6\n16\n-4\n"
+        );
+    }
+
+    #[test]
+    fn shifts_out_of_range_are_runtime_errors() {
+        for source in ["print 1 << 64;", "print 1 << -1;", "print 1 >> 64;"] {
+            assert_eq!(
+                runtime_error(source).message,
+                "Shift amount must be between 0 and 63."
+            );
+        }
+        assert_eq!(
+            runtime_error("print 1.0 & 1;").message,
+            "Operands must be integers."
+        );
+    }
+
+    #[test]
+    fn unclosed_input_is_incomplete() {
+        for source in [
+            "fun f() {",
+            "print (1 +",
+            "print \"abc",
+            "print \"abc\\",
+            "/* comment",
+            "print \"a ${1 +",
+            "print \"a ${ {",
+        ] {
+            assert!(Interpreter::is_incomplete(source), "{:?}", source);
+        }
+        for source in [
+            "print 1",
+            "print \"a ${1} b\";",
+            "}",
+            "print @",
+            "print \"\\q\";",
+        ] {
+            assert!(!Interpreter::is_incomplete(source), "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn non_finite_floats_print_like_lox() {
+        let source = "
+            var big = 10.0 ** 400;
+            print big; print -big; print big - big;
+            print str(big) + \" \" + str(-big);
+            print 2.5; print 3.0;
+        ";
+        assert_eq!(
+            output(source),
+            "Infinity\n-Infinity\nNaN\nInfinity -Infinity\n2.5\n3\n"
+        );
+    }
+
+    #[test]
+    fn num_accepts_only_finite_numbers() {
+        assert_eq!(
+            output("print num(\" 42 \"); print num(\"-2.5\"); print num(7);"),
+            "42\n-2.5\n7\n"
+        );
+        for text in ["inf", "-infinity", "NaN", "1e999", "12abc"] {
+            let error = runtime_error(&format!("num(\"{}\");", text));
+            assert_eq!(
+                error.message,
+                format!("Can't convert '{}' to a number.", text)
+            );
+        }
+    }
+
+    #[test]
+    fn closures_keep_their_captured_variables() {
+        let source = "
+            fun makeCounter() {
+                var i = 0;
+                fun count() {
+                    i = i + 1;
+                    print i;
+                }
+                return count;
+            }
+            var counter = makeCounter();
+            counter();
+            counter();
+            counter();
+            var other = makeCounter();
+            other();
+        ";
+        assert_eq!(output(source), "1\n2\n3\n1\n");
+    }
+
+    #[test]
+    fn methods_are_inherited_and_super_binds_this() {
+        let source = "
+            class A {
+                name() { return \"A\"; }
+                greet() { return \"I am \" + this.name(); }
+            }
+            class B < A {}
+            class C < B {
+                name() { return \"C\"; }
+                greet() { return super.greet() + \" via \" + super.name(); }
+            }
+            print B().greet();
+            print C().greet();
+            var greet = C().greet;
+            print greet();
+        ";
+        assert_eq!(output(source), "I am A\nI am C via A\nI am C via A\n");
+    }
+
+    #[test]
+    fn superclasses_must_be_classes() {
+        let error = runtime_error("var NotAClass = 1;\nclass A < NotAClass {}");
+        assert_eq!(error.message, "Superclass must be a class.");
+        assert_eq!(
+            compile_errors("class A { f() { return super.f(); } }"),
+            ["Can't use 'super' in a class with no superclass."]
+        );
+        assert_eq!(
+            compile_errors("print super.f;"),
+            ["Can't use 'super' outside of a class."]
+        );
+    }
+
+    #[test]
+    fn parser_recovers_and_reports_every_syntax_error() {
+        assert_eq!(
+            compile_errors("print (1; print 2 +; var = 3;"),
+            [
+                "Expect ')' after expression.",
+                "Expect expression.",
+                "Expect variable name.",
+            ]
+        );
+        // Recovery resumes at the next statement, which parses normally.
+        assert_eq!(
+            compile_errors("var a = ;\nfun f() { return 1; }\nclass { }\nprint f();"),
+            ["Expect expression.", "Expect class name."]
+        );
+    }
+
+    #[test]
+    fn interpolated_strings_evaluate_their_parts() {
+        let source = r#"
+            var x = "in";
+            var n = 2;
+            print "a ${ "b ${x} c" } d";
+            print "${n} + ${n} = ${n + n}, \${n}";
+        "#;
+        assert_eq!(output(source), "a b in c d\n2 + 2 = 4, ${n}\n");
     }
 }
@@ -0,0 +1,75 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{runtime_error::RuntimeError, value::Value, Interpreter};
+
+/// Defines the built-in native functions in `interpreter`'s globals.
+pub fn define_all(interpreter: &Interpreter) {
+    interpreter.define_native("clock", 0, |_, _| clock());
+    interpreter.define_native("type", 1, |_, arguments| {
+        Ok(Value::String(arguments[0].type_name().to_string()))
+    });
+    interpreter.define_native("str", 1, |_, arguments| {
+        Ok(Value::String(arguments[0].to_string()))
+    });
+    interpreter.define_native("num", 1, |_, arguments| num(&arguments[0]));
+    interpreter.define_native("len", 1, |_, arguments| len(&arguments[0]));
+    interpreter.define_native("input", 0, |interpreter, _| input(interpreter));
+}
+
+/// Seconds since the Unix epoch, for timing scripts.
+fn clock() -> Result<Value, RuntimeError> {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| RuntimeError::native(&format!("System clock error. {}", e)))?;
+    Ok(Value::Float(elapsed.as_secs_f64()))
+}
+
+/// Converts a string to a number: an integer if it reads as one, otherwise a
+/// float. Numbers are returned unchanged. Strings such as "inf", "NaN" or
+/// "1e999" that only parse to non-finite floats are rejected.
+fn num(value: &Value) -> Result<Value, RuntimeError> {
+    match value {
+        Value::Integer(_) | Value::Float(_) => Ok(value.clone()),
+        Value::String(text) => {
+            let text = text.trim();
+            let float = || {
+                text.parse::<f64>()
+                    .ok()
+                    .filter(|float| float.is_finite())
+                    .map(Value::Float)
+            };
+            text.parse::<i64>()
+                .map(Value::Integer)
+                .ok()
+                .or_else(float)
+                .ok_or_else(|| {
+                    RuntimeError::native(&format!("Can't convert '{}' to a number.", text))
+                })
+        }
+        _ => Err(RuntimeError::native(&format!(
+            "Can't convert {} to a number.",
+            value.type_name()
+        ))),
+    }
+}
+
+/// Length of a string in characters, or the number of items in a list or map.
+fn len(value: &Value) -> Result<Value, RuntimeError> {
+    match value {
+        Value::String(text) => Ok(Value::Integer(text.chars().count() as i64)),
+        Value::List(list) => Ok(Value::Integer(list.borrow().len() as i64)),
+        Value::Map(map) => Ok(Value::Integer(map.borrow().len() as i64)),
+        _ => Err(RuntimeError::native(&format!(
+            "Can't take the length of {}.",
+            value.type_name()
+        ))),
+    }
+}
+
+/// Reads a line of input without its line ending, or `nil` at end of input.
+fn input(interpreter: &Interpreter) -> Result<Value, RuntimeError> {
+    let line = interpreter
+        .read_line()
+        .map_err(|e| RuntimeError::native(&format!("Failed to read input. {}", e)))?;
+    Ok(line.map_or(Value::Nil, Value::String))
+}
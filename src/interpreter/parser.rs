@@ -1,17 +1,27 @@
-use std::cell::{Cell, RefCell};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 
 use super::{
     error::LoxError,
     expression::Expr,
-    statement::Statement,
-    token::Token,
+    statement::{FunctionDecl, Statement},
+    token::{Span, Token},
     token_type::{Literal, TokenType},
 };
 
+/// Upper bound on function parameters and call arguments.
+const MAX_ARITY: usize = 255;
+
 #[derive(Debug)]
 pub struct Parser {
     tokens: RefCell<Vec<Token>>,
     current: Cell<usize>,
+    errors: RefCell<Vec<LoxError>>,
+    /// Whether an expression statement ending the source may leave out its
+    /// `;`, so that `1 + 2` can be evaluated on its own.
+    trailing_expression: bool,
 }
 
 impl Parser {
@@ -19,24 +29,123 @@ impl Parser {
         Parser {
             tokens: RefCell::new(tokens),
             current: Cell::new(0),
+            errors: RefCell::new(Vec::new()),
+            trailing_expression: false,
         }
     }
 
-    pub fn parse(&self) -> Result<Vec<Statement>, LoxError> {
+    /// Lets the final expression statement omit its `;`.
+    pub fn with_trailing_expression(mut self) -> Self {
+        self.trailing_expression = true;
+        self
+    }
+
+    /// Parses the whole token stream, recovering after each syntax error so
+    /// that every error in the source is reported at once.
+    pub fn parse(&self) -> Result<Vec<Statement>, Vec<LoxError>> {
         let mut statements = Vec::new();
         while !self.is_at_end() {
-            statements.push(self.declaration()?);
+            if let Some(stmt) = self.declaration() {
+                statements.push(stmt);
+            }
+        }
+
+        let errors = self.errors.take();
+        if errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(errors)
         }
-        Ok(statements)
     }
 
-    fn declaration(&self) -> Result<Statement, LoxError> {
+    fn declaration(&self) -> Option<Statement> {
+        match self.try_declaration() {
+            Ok(stmt) => Some(stmt),
+            Err(error) => {
+                self.errors.borrow_mut().push(error);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn try_declaration(&self) -> Result<Statement, LoxError> {
+        if self.match_type(TokenType::Class) {
+            return self.class_declaration();
+        }
+        if self.match_type(TokenType::Fun) {
+            return self.function("function");
+        }
         if self.match_type(TokenType::Var) {
             return self.var_declaration();
         }
-        return self.statement();
+        self.statement()
+    }
+
+    fn class_declaration(&self) -> Result<Statement, LoxError> {
+        let start = self.previous().span;
+        let name = self.consume(TokenType::Identifier, "Expect class name.")?;
+
+        let mut superclass = None;
+        if self.match_type(TokenType::Less) {
+            let superclass_name = self.consume(TokenType::Identifier, "Expect superclass name.")?;
+            superclass = Some(Expr::Variable(superclass_name, Cell::new(None)));
+        }
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.function_declaration("method")?);
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+        Ok(Statement::Class(
+            name,
+            superclass,
+            methods,
+            self.span_from(start),
+        ))
+    }
+
+    fn function(&self, kind: &str) -> Result<Statement, LoxError> {
+        let start = self.previous().span;
+        let declaration = self.function_declaration(kind)?;
+        Ok(Statement::Function(declaration, self.span_from(start)))
     }
+
+    fn function_declaration(&self, kind: &str) -> Result<Rc<FunctionDecl>, LoxError> {
+        let name = self.consume(TokenType::Identifier, &format!("Expect {} name.", kind))?;
+        self.consume(
+            TokenType::LeftParen,
+            &format!("Expect '(' after {} name.", kind),
+        )?;
+        let mut params = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= MAX_ARITY {
+                    self.error(
+                        &self.peek(),
+                        &format!("Can't have more than {} parameters.", MAX_ARITY),
+                    );
+                }
+                params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
+                if !self.match_type(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("Expect '{{' before {} body.", kind),
+        )?;
+        let body = self.block()?;
+        Ok(Rc::new(FunctionDecl { name, params, body }))
+    }
+
     fn var_declaration(&self) -> Result<Statement, LoxError> {
+        let start = self.previous().span;
         let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
         let mut initializer = None;
         if self.match_type(TokenType::Equal) {
@@ -45,31 +154,195 @@ impl Parser {
         self.consume(
             TokenType::Semicolon,
             "Expect ';' after variable declaration.",
-        );
-        Ok(Statement::Var(name, initializer))
+        )?;
+        Ok(Statement::Var(name, initializer, self.span_from(start)))
     }
 
     fn statement(&self) -> Result<Statement, LoxError> {
+        if self.match_type(TokenType::For) {
+            return self.for_statement();
+        }
+        if self.match_type(TokenType::If) {
+            return self.if_statement();
+        }
         if self.match_type(TokenType::Print) {
             return self.print_statement();
         }
+        if self.match_type(TokenType::Return) {
+            return self.return_statement();
+        }
+        if self.match_type(TokenType::While) {
+            return self.while_statement();
+        }
+        if self.match_type(TokenType::LeftBrace) {
+            let start = self.previous().span;
+            let statements = self.block()?;
+            return Ok(Statement::Block(statements, self.span_from(start)));
+        }
         self.expression_statement()
     }
 
+    fn block(&self) -> Result<Vec<Statement>, LoxError> {
+        let mut statements = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if let Some(stmt) = self.declaration() {
+                statements.push(stmt);
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
+    }
+
+    /// Desugars `for (init; cond; incr) body` into
+    /// `{ init; while (cond) { body; incr; } }`.
+    fn for_statement(&self) -> Result<Statement, LoxError> {
+        let start = self.previous().span;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.match_type(TokenType::Semicolon) {
+            None
+        } else if self.match_type(TokenType::Var) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if !self.check(TokenType::Semicolon) {
+            self.expression()?
+        } else {
+            Expr::Literal(Literal::Bool(true), self.peek().span)
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+
+        let increment = if !self.check(TokenType::RightParen) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+
+        let mut body = self.statement()?;
+        if let Some(increment) = increment {
+            let span = body.span().merge(increment.span());
+            let increment = Statement::Expression(Box::new(increment.clone()), increment.span());
+            body = Statement::Block(vec![body, increment], span);
+        }
+        let span = self.span_from(start);
+        body = Statement::While(Box::new(condition), Box::new(body), span);
+        if let Some(initializer) = initializer {
+            body = Statement::Block(vec![initializer, body], span);
+        }
+        Ok(body)
+    }
+
+    fn if_statement(&self) -> Result<Statement, LoxError> {
+        let start = self.previous().span;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
+
+        let then_branch = self.statement()?;
+        // Binding the `else` here attaches it to the nearest `if`.
+        let else_branch = if self.match_type(TokenType::Else) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+        Ok(Statement::If(
+            Box::new(condition),
+            Box::new(then_branch),
+            else_branch,
+            self.span_from(start),
+        ))
+    }
+
+    fn return_statement(&self) -> Result<Statement, LoxError> {
+        let keyword = self.previous();
+        let value = if !self.check(TokenType::Semicolon) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        let span = self.span_from(keyword.span);
+        Ok(Statement::Return(keyword, value, span))
+    }
+
+    fn while_statement(&self) -> Result<Statement, LoxError> {
+        let start = self.previous().span;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+        let body = self.statement()?;
+        Ok(Statement::While(
+            Box::new(condition),
+            Box::new(body),
+            self.span_from(start),
+        ))
+    }
+
     fn expression_statement(&self) -> Result<Statement, LoxError> {
         let value = self.expression()?;
+        if self.trailing_expression && self.is_at_end() {
+            let span = value.span();
+            return Ok(Statement::Expression(Box::new(value), span));
+        }
         self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
-        Ok(Statement::Expression(Box::new(value)))
+        let span = self.span_from(value.span());
+        Ok(Statement::Expression(Box::new(value), span))
     }
 
     fn print_statement(&self) -> Result<Statement, LoxError> {
+        let start = self.previous().span;
         let value = self.expression()?;
         self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
-        Ok(Statement::Print(Box::new(value)))
+        Ok(Statement::Print(Box::new(value), self.span_from(start)))
     }
 
     pub fn expression(&self) -> Result<Expr, LoxError> {
-        self.equality()
+        self.assignment()
+    }
+
+    fn assignment(&self) -> Result<Expr, LoxError> {
+        let expr = self.or()?;
+
+        if self.match_type(TokenType::Equal) {
+            let equals = self.previous();
+            let value = self.assignment()?;
+
+            match expr {
+                Expr::Variable(name, _) => {
+                    return Ok(Expr::Assign(name, Box::new(value), Cell::new(None)))
+                }
+                Expr::Get(object, name) => return Ok(Expr::Set(object, name, Box::new(value))),
+                // The parser is not confused here, so report without unwinding.
+                _ => self.error(&equals, "Invalid assignment target."),
+            }
+            return Ok(value);
+        }
+        Ok(expr)
+    }
+
+    fn or(&self) -> Result<Expr, LoxError> {
+        let mut expr = self.and()?;
+
+        while self.match_type(TokenType::Or) {
+            let operator = self.previous();
+            let right = self.and()?;
+            expr = Expr::Logical(Box::new(expr), operator, Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn and(&self) -> Result<Expr, LoxError> {
+        let mut expr = self.equality()?;
+
+        while self.match_type(TokenType::And) {
+            let operator = self.previous();
+            let right = self.equality()?;
+            expr = Expr::Logical(Box::new(expr), operator, Box::new(right));
+        }
+        Ok(expr)
     }
 
     pub fn equality(&self) -> Result<Expr, LoxError> {
@@ -85,7 +358,7 @@ impl Parser {
     }
 
     fn comparison(&self) -> Result<Expr, LoxError> {
-        let mut expr = self.term()?;
+        let mut expr = self.bit_or()?;
 
         while self.match_types(vec![
             TokenType::Greater,
@@ -93,6 +366,56 @@ impl Parser {
             TokenType::Less,
             TokenType::LessEqual,
         ]) {
+            let operator = self.previous();
+            let right = self.bit_or()?;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+        }
+
+        Ok(expr)
+    }
+
+    // Bitwise operators sit between comparison and term, in Rust's order:
+    // `|` binds loosest, then `^`, then `&`, then the shifts.
+    fn bit_or(&self) -> Result<Expr, LoxError> {
+        let mut expr = self.bit_xor()?;
+
+        while self.match_type(TokenType::Pipe) {
+            let operator = self.previous();
+            let right = self.bit_xor()?;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+        }
+
+        Ok(expr)
+    }
+
+    fn bit_xor(&self) -> Result<Expr, LoxError> {
+        let mut expr = self.bit_and()?;
+
+        while self.match_type(TokenType::Caret) {
+            let operator = self.previous();
+            let right = self.bit_and()?;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+        }
+
+        Ok(expr)
+    }
+
+    fn bit_and(&self) -> Result<Expr, LoxError> {
+        let mut expr = self.shift()?;
+
+        while self.match_type(TokenType::Ampersand) {
+            let operator = self.previous();
+            let right = self.shift()?;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+        }
+
+        Ok(expr)
+    }
+
+    fn shift(&self) -> Result<Expr, LoxError> {
+        let mut expr = self.term()?;
+
+        while self.match_types(vec![TokenType::LessLess, TokenType::GreaterGreater]) {
             let operator = self.previous();
             let right = self.term()?;
             expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
@@ -115,7 +438,12 @@ impl Parser {
     fn factor(&self) -> Result<Expr, LoxError> {
         let mut expr = self.unary()?;
 
-        while self.match_types(vec![TokenType::Slash, TokenType::Star]) {
+        while self.match_types(vec![
+            TokenType::Slash,
+            TokenType::Star,
+            TokenType::Percent,
+            TokenType::TildeSlash,
+        ]) {
             let operator = self.previous();
             let right = self.unary()?;
             expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
@@ -125,55 +453,168 @@ impl Parser {
     }
 
     fn unary(&self) -> Result<Expr, LoxError> {
-        if self.match_types(vec![TokenType::Bang, TokenType::Minus]) {
+        if self.match_types(vec![TokenType::Bang, TokenType::Minus, TokenType::Tilde]) {
             let operator = self.previous();
             let right = self.unary()?;
             return Ok(Expr::Unary(operator, Box::new(right)));
         }
-        self.primary()
+        self.exponent()
+    }
+
+    /// `**` binds tighter than unary operators on its left, so `-2 ** 2` is
+    /// `-(2 ** 2)`, and is right-associative through its unary operand.
+    fn exponent(&self) -> Result<Expr, LoxError> {
+        let expr = self.call()?;
+        if self.match_type(TokenType::StarStar) {
+            let operator = self.previous();
+            let right = self.unary()?;
+            return Ok(Expr::Binary(Box::new(expr), operator, Box::new(right)));
+        }
+        Ok(expr)
+    }
+
+    fn call(&self) -> Result<Expr, LoxError> {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.match_type(TokenType::LeftParen) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_type(TokenType::Dot) {
+                let name =
+                    self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+                expr = Expr::Get(Box::new(expr), name);
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    fn finish_call(&self, callee: Expr) -> Result<Expr, LoxError> {
+        let mut arguments = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARITY {
+                    self.error(
+                        &self.peek(),
+                        &format!("Can't have more than {} arguments.", MAX_ARITY),
+                    );
+                }
+                arguments.push(self.expression()?);
+                if !self.match_type(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+        Ok(Expr::Call(Box::new(callee), paren, arguments))
     }
 
     fn primary(&self) -> Result<Expr, LoxError> {
         if self.match_type(TokenType::False) {
-            return Ok(Expr::Literal(Literal::Bool(false)));
+            return Ok(Expr::Literal(Literal::Bool(false), self.previous().span));
         }
         if self.match_type(TokenType::True) {
-            return Ok(Expr::Literal(Literal::Bool(true)));
+            return Ok(Expr::Literal(Literal::Bool(true), self.previous().span));
         }
         if self.match_type(TokenType::None) {
-            return Ok(Expr::Literal(Literal::None));
+            return Ok(Expr::Literal(Literal::None, self.previous().span));
         }
 
         if self.match_types(vec![TokenType::Number, TokenType::String]) {
-            return Ok(Expr::Literal(self.previous().literal));
+            let token = self.previous();
+            return Ok(Expr::Literal(token.literal, token.span));
+        }
+
+        if self.match_type(TokenType::Interpolation) {
+            return self.interpolation();
+        }
+
+        if self.match_type(TokenType::Super) {
+            let keyword = self.previous();
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+            let method = self.consume(TokenType::Identifier, "Expect superclass method name.")?;
+            return Ok(Expr::Super(keyword, method, Cell::new(None)));
+        }
+
+        if self.match_type(TokenType::This) {
+            return Ok(Expr::This(self.previous(), Cell::new(None)));
         }
 
         if self.match_type(TokenType::Identifier) {
-            return Ok(Expr::Variable(self.previous()));
+            return Ok(Expr::Variable(self.previous(), Cell::new(None)));
         }
 
         if self.match_type(TokenType::LeftParen) {
+            let left = self.previous();
             let expr = self.expression()?;
-            self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
-            return Ok(Expr::Grouping(Box::new(expr)));
+            let right = self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+            return Ok(Expr::Grouping(Box::new(expr), left.span.merge(right.span)));
         }
-        Err(LoxError::new(
-            self.peek().line,
-            Some(self.peek().lexeme),
-            "Expect expression.",
-        ))
+        Err(LoxError::at(&self.peek(), "Expect expression."))
+    }
+
+    /// Parses the rest of an interpolated string, starting just after its
+    /// first `Interpolation` token and ending at the closing `String` token.
+    fn interpolation(&self) -> Result<Expr, LoxError> {
+        let mut parts = vec![];
+        loop {
+            let segment = self.previous();
+            parts.push(Expr::Literal(segment.literal, segment.span));
+            if segment.ttype == TokenType::String {
+                return Ok(Expr::Interpolation(parts));
+            }
+            parts.push(self.expression()?);
+            if !self.match_types(vec![TokenType::Interpolation, TokenType::String]) {
+                return Err(LoxError::at(
+                    &self.peek(),
+                    "Expect '}' after interpolated expression.",
+                ));
+            }
+        }
+    }
+
+    /// Span from `start` to the end of the last consumed token.
+    fn span_from(&self, start: Span) -> Span {
+        start.merge(self.previous().span)
     }
 
     fn consume(&self, ttype: TokenType, message: &str) -> Result<Token, LoxError> {
         if self.check(ttype) {
             return Ok(self.advance());
         }
-        Err(LoxError::new(
-            self.peek().line,
-            Some(self.peek().lexeme),
-            message,
-        ))
+        Err(LoxError::at(&self.peek(), message))
+    }
+    /// Records an error that does not leave the parser in a confused state,
+    /// so parsing carries on without synchronizing.
+    fn error(&self, token: &Token, message: &str) {
+        self.errors.borrow_mut().push(LoxError::at(token, message));
     }
+
+    /// Discards tokens until the start of the next statement, so one syntax
+    /// error does not cascade into many.
+    fn synchronize(&self) {
+        self.advance();
+        while !self.is_at_end() {
+            if self.previous().ttype == TokenType::Semicolon {
+                return;
+            }
+            match self.peek().ttype {
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return => return,
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+    }
+
     fn match_type(&self, ttype: TokenType) -> bool {
         if self.check(ttype) {
             self.advance();
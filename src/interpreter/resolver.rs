@@ -0,0 +1,316 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
+
+use super::{
+    error::LoxError,
+    expression::{Depth, Expr, ExprVisitor},
+    statement::{FunctionDecl, Statement, StmtVisitor},
+    token::Token,
+    token_type::Literal,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// Static pass run between parsing and interpretation. It records, on every
+/// variable reference, how many scopes away its binding lives, and rejects
+/// programs that misuse local variables or `return`.
+pub struct Resolver {
+    /// One map per local scope, innermost last. A name maps to `false` while
+    /// its initializer is being resolved and `true` once it is defined.
+    scopes: RefCell<Vec<HashMap<String, bool>>>,
+    /// Errors found so far. None of them stop resolution, so all are reported.
+    errors: RefCell<Vec<LoxError>>,
+    current_function: Cell<FunctionType>,
+    current_class: Cell<ClassType>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            scopes: RefCell::new(Vec::new()),
+            errors: RefCell::new(Vec::new()),
+            current_function: Cell::new(FunctionType::None),
+            current_class: Cell::new(ClassType::None),
+        }
+    }
+
+    pub fn resolve(&self, statements: &[Statement]) -> Result<(), Vec<LoxError>> {
+        self.resolve_statements(statements);
+        let errors = self.errors.take();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn resolve_statements(&self, statements: &[Statement]) {
+        for stmt in statements {
+            stmt.accept(self);
+        }
+    }
+
+    fn resolve_expr(&self, expr: &Expr) {
+        expr.accept(self)
+    }
+
+    fn resolve_function(&self, declaration: &FunctionDecl, function_type: FunctionType) {
+        let enclosing_function = self.current_function.replace(function_type);
+        self.begin_scope();
+        for param in &declaration.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_statements(&declaration.body);
+        self.end_scope();
+        self.current_function.set(enclosing_function);
+    }
+
+    fn resolve_local(&self, name: &Token, depth: &Depth) {
+        let scopes = self.scopes.borrow();
+        let found = scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&name.lexeme));
+        depth.set(found);
+    }
+
+    fn begin_scope(&self) {
+        self.scopes.borrow_mut().push(HashMap::new());
+    }
+
+    fn end_scope(&self) {
+        self.scopes.borrow_mut().pop();
+    }
+
+    fn declare(&self, name: &Token) {
+        let mut scopes = self.scopes.borrow_mut();
+        if let Some(scope) = scopes.last_mut() {
+            if scope.contains_key(&name.lexeme) {
+                self.error(name, "Already a variable with this name in this scope.");
+            }
+            scope.insert(name.lexeme.clone(), false);
+        }
+    }
+
+    fn define(&self, name: &Token) {
+        if let Some(scope) = self.scopes.borrow_mut().last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    fn error(&self, token: &Token, message: &str) {
+        self.errors.borrow_mut().push(LoxError::at(token, message));
+    }
+}
+
+impl ExprVisitor for Resolver {
+    type Res = ();
+    fn visit_assign(&self, name: &Token, value: &Expr, depth: &Depth) -> Self::Res {
+        self.resolve_expr(value);
+        self.resolve_local(name, depth);
+    }
+
+    fn visit_binary(&self, left: &Expr, _operator: &Token, right: &Expr) -> Self::Res {
+        self.resolve_expr(left);
+        self.resolve_expr(right)
+    }
+
+    fn visit_call(&self, callee: &Expr, _paren: &Token, arguments: &[Expr]) -> Self::Res {
+        self.resolve_expr(callee);
+        for argument in arguments {
+            self.resolve_expr(argument);
+        }
+    }
+
+    fn visit_get(&self, object: &Expr, _name: &Token) -> Self::Res {
+        self.resolve_expr(object)
+    }
+
+    fn visit_grouping(&self, expr: &Expr) -> Self::Res {
+        self.resolve_expr(expr)
+    }
+
+    fn visit_interpolation(&self, parts: &[Expr]) -> Self::Res {
+        for part in parts {
+            self.resolve_expr(part);
+        }
+    }
+
+    fn visit_literal(&self, _literal: &Literal) -> Self::Res {}
+
+    fn visit_logical(&self, left: &Expr, _operator: &Token, right: &Expr) -> Self::Res {
+        self.resolve_expr(left);
+        self.resolve_expr(right)
+    }
+
+    fn visit_set(&self, object: &Expr, _name: &Token, value: &Expr) -> Self::Res {
+        self.resolve_expr(value);
+        self.resolve_expr(object)
+    }
+
+    fn visit_super(&self, keyword: &Token, _method: &Token, depth: &Depth) -> Self::Res {
+        let message = match self.current_class.get() {
+            ClassType::Subclass => None,
+            ClassType::Class => Some("Can't use 'super' in a class with no superclass."),
+            ClassType::None => Some("Can't use 'super' outside of a class."),
+        };
+        if let Some(message) = message {
+            self.error(keyword, message);
+        }
+        self.resolve_local(keyword, depth);
+    }
+
+    fn visit_this(&self, keyword: &Token, depth: &Depth) -> Self::Res {
+        if self.current_class.get() == ClassType::None {
+            self.error(keyword, "Can't use 'this' outside of a class.");
+        }
+        self.resolve_local(keyword, depth);
+    }
+
+    fn visit_unary(&self, _operator: &Token, expr: &Expr) -> Self::Res {
+        self.resolve_expr(expr)
+    }
+
+    fn visit_variable(&self, name: &Token, depth: &Depth) -> Self::Res {
+        let declared_only = self
+            .scopes
+            .borrow()
+            .last()
+            .and_then(|scope| scope.get(&name.lexeme).copied())
+            == Some(false);
+        if declared_only {
+            self.error(name, "Can't read local variable in its own initializer.");
+        }
+        self.resolve_local(name, depth);
+    }
+}
+
+impl StmtVisitor for Resolver {
+    type Res = ();
+    fn visit_block_stmt(&self, statements: &[Statement]) -> Self::Res {
+        self.begin_scope();
+        self.resolve_statements(statements);
+        self.end_scope();
+    }
+
+    fn visit_class_stmt(
+        &self,
+        name: &Token,
+        superclass: &Option<Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> Self::Res {
+        self.declare(name);
+        self.define(name);
+
+        if let Some(Expr::Variable(superclass_name, _)) = superclass {
+            if superclass_name.lexeme == name.lexeme {
+                self.error(superclass_name, "A class can't inherit from itself.");
+            }
+        }
+        if let Some(superclass) = superclass {
+            self.resolve_expr(superclass);
+        }
+
+        let enclosing_class = self.current_class.replace(if superclass.is_some() {
+            ClassType::Subclass
+        } else {
+            ClassType::Class
+        });
+
+        if superclass.is_some() {
+            self.begin_scope();
+            if let Some(scope) = self.scopes.borrow_mut().last_mut() {
+                scope.insert("super".to_string(), true);
+            }
+        }
+
+        self.begin_scope();
+        if let Some(scope) = self.scopes.borrow_mut().last_mut() {
+            scope.insert("this".to_string(), true);
+        }
+        for method in methods {
+            let function_type = if method.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(method, function_type);
+        }
+        self.end_scope();
+        if superclass.is_some() {
+            self.end_scope();
+        }
+
+        self.current_class.set(enclosing_class);
+    }
+
+    fn visit_print_stmt(&self, expr: &Expr) -> Self::Res {
+        self.resolve_expr(expr)
+    }
+
+    fn visit_expression_stmt(&self, expr: &Expr) -> Self::Res {
+        self.resolve_expr(expr)
+    }
+
+    fn visit_function_stmt(&self, declaration: &Rc<FunctionDecl>) -> Self::Res {
+        // Define eagerly so the function can refer to itself recursively.
+        self.declare(&declaration.name);
+        self.define(&declaration.name);
+        self.resolve_function(declaration, FunctionType::Function)
+    }
+
+    fn visit_if_stmt(
+        &self,
+        condition: &Expr,
+        then_branch: &Statement,
+        else_branch: &Option<Box<Statement>>,
+    ) -> Self::Res {
+        self.resolve_expr(condition);
+        then_branch.accept(self);
+        if let Some(else_branch) = else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_return_stmt(&self, keyword: &Token, value: &Option<Expr>) -> Self::Res {
+        if self.current_function.get() == FunctionType::None {
+            self.error(keyword, "Can't return from top-level code.");
+        }
+        if let Some(value) = value {
+            if self.current_function.get() == FunctionType::Initializer {
+                self.error(keyword, "Can't return a value from an initializer.");
+            }
+            self.resolve_expr(value);
+        }
+    }
+
+    fn visit_var_stmt(&self, name: &Token, initializer: &Option<Expr>) -> Self::Res {
+        self.declare(name);
+        if let Some(initializer) = initializer {
+            self.resolve_expr(initializer);
+        }
+        self.define(name);
+    }
+
+    fn visit_while_stmt(&self, condition: &Expr, body: &Statement) -> Self::Res {
+        self.resolve_expr(condition);
+        body.accept(self)
+    }
+}
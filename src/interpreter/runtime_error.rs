@@ -0,0 +1,99 @@
+use core::fmt;
+use std::fmt::Display;
+
+use super::{
+    error::{source_line, write_snippet},
+    token::{Span, Token},
+    value::Value,
+};
+
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub message: String,
+    /// Source the error points at; the offending token unless widened.
+    pub span: Span,
+    /// Text of the offending line, once attached with `with_source`.
+    source_line: Option<String>,
+}
+
+impl RuntimeError {
+    pub fn new(token: &Token, message: &str) -> Self {
+        RuntimeError {
+            message: message.to_string(),
+            span: token.span,
+            source_line: None,
+        }
+    }
+
+    /// An error raised by a native function, which has no source of its
+    /// own. The call expression's span is filled in by `with_default_span`.
+    pub fn native(message: &str) -> Self {
+        RuntimeError {
+            message: message.to_string(),
+            span: Span::default(),
+            source_line: None,
+        }
+    }
+
+    /// Points an error that has no location yet, such as a native
+    /// function's, at `span`. Errors that already have one are left alone.
+    pub fn with_default_span(self, span: Span) -> Self {
+        if self.span == Span::default() {
+            self.with_span(span)
+        } else {
+            self
+        }
+    }
+
+    /// Points the error at `span`, e.g. a whole expression rather than
+    /// just its operator.
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+
+    /// Attaches the offending line of `source`, if the error points into it.
+    /// Errors raised in a function defined by an earlier `run` point into
+    /// that run's source instead, and are left without a line.
+    pub fn with_source(mut self, source: &str, source_id: usize) -> Self {
+        if self.span != Span::default() && self.span.source_id == source_id {
+            self.source_line = source_line(source, &self.span);
+        }
+        self
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Errors raised outside any Lox source, e.g. calling a missing global
+        // from Rust, have no location to show.
+        if self.span == Span::default() {
+            return write!(f, "Runtime error: {}", self.message);
+        }
+        write!(
+            f,
+            "[line {}:{}] Runtime error: {}",
+            self.span.line, self.span.column, self.message
+        )?;
+        if let Some(source_line) = &self.source_line {
+            write_snippet(f, source_line, &self.span)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// Ways statement execution can leave its block early: a runtime error, or a
+/// `return` unwinding to the function call that is running it.
+#[derive(Debug)]
+pub enum Unwind {
+    Error(RuntimeError),
+    Return(Value),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
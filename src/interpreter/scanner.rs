@@ -5,18 +5,35 @@ use std::{
 
 use super::{
     error::LoxError,
-    token::Token,
+    token::{Span, Token},
     token_type::{Literal, TokenType},
 };
 
+/// Turns source text into tokens. All positions (`start`, `current`,
+/// `line_start`) are byte offsets into `source` and always sit on UTF-8
+/// character boundaries, so slicing `source` with them is safe.
 pub struct Scanner {
     source: String,
-    source_chars: Vec<char>,
+    /// Stamped on every span, see `Span::source_id`.
+    source_id: usize,
     tokens: RefCell<Vec<Token>>,
 
     start: Cell<usize>,
     current: Cell<usize>,
     line: Cell<usize>,
+    /// Position where the current line begins, for computing columns.
+    line_start: Cell<usize>,
+    /// Line and column where the token being scanned begins.
+    start_line: Cell<usize>,
+    start_column: Cell<usize>,
+    /// One entry per string interpolation (`"${`) we are currently inside,
+    /// counting the unclosed `{` seen within it so the matching `}` can
+    /// resume the string.
+    interpolations: RefCell<Vec<usize>>,
+    /// Set when the source ends inside a string or block comment.
+    unterminated: Cell<bool>,
+    /// Empty span just past the last token scanned, if any.
+    token_end: Cell<Option<Span>>,
 
     keywords: RefCell<HashMap<String, TokenType>>,
 }
@@ -24,15 +41,28 @@ pub struct Scanner {
 impl Scanner {
     pub fn new(source: String) -> Self {
         Scanner {
-            source: source.clone(),
-            source_chars: source.chars().collect(),
+            source,
+            source_id: 0,
             tokens: RefCell::new(vec![]),
             start: Cell::new(0),
             current: Cell::new(0),
             line: Cell::new(1),
+            line_start: Cell::new(0),
+            start_line: Cell::new(1),
+            start_column: Cell::new(1),
+            interpolations: RefCell::new(vec![]),
+            unterminated: Cell::new(false),
+            token_end: Cell::new(None),
             keywords: RefCell::new(Self::init_keywords()),
         }
     }
+
+    /// Marks the spans of the scanned tokens as coming from source `source_id`.
+    pub fn with_source_id(mut self, source_id: usize) -> Self {
+        self.source_id = source_id;
+        self
+    }
+
     fn init_keywords() -> HashMap<String, TokenType> {
         let mut keywords = HashMap::new();
         let keyword_tokens = [
@@ -63,33 +93,73 @@ impl Scanner {
 
     pub fn scan(&self) -> Result<Vec<Token>, LoxError> {
         while !self.end_of_source() {
-            self.start.set(self.current.get());
+            self.start_token();
             self.scan_token()?;
         }
 
-        self.tokens.borrow_mut().push(Token::new(
-            TokenType::Eof,
-            "".into(),
-            Literal::None,
-            self.line.get(),
-        ));
+        // Put end of input right after the last token rather than after any
+        // trailing blank lines, so errors there point at a line with source.
+        self.start_token();
+        let span = self.token_end.get().unwrap_or_else(|| self.span());
+        self.tokens
+            .borrow_mut()
+            .push(Token::new(TokenType::Eof, "".into(), Literal::None, span));
 
         Ok(self.tokens.borrow().to_vec())
     }
 
+    /// Whether the source scanned so far ends in the middle of a string,
+    /// block comment or `${...}` interpolation, so more input could
+    /// complete it.
+    pub fn is_unterminated(&self) -> bool {
+        self.unterminated.get() || !self.interpolations.borrow().is_empty()
+    }
+
     fn scan_token(&self) -> Result<(), LoxError> {
         let c = self.advance();
         match c {
             '(' => self.put_token(TokenType::LeftParen),
             ')' => self.put_token(TokenType::RightParen),
-            '{' => self.put_token(TokenType::LeftBrace),
-            '}' => self.put_token(TokenType::RightBrace),
+            '{' => {
+                if let Some(depth) = self.interpolations.borrow_mut().last_mut() {
+                    *depth += 1;
+                }
+                self.put_token(TokenType::LeftBrace)
+            }
+            '}' => {
+                let mut interpolations = self.interpolations.borrow_mut();
+                match interpolations.last_mut() {
+                    Some(0) => {
+                        interpolations.pop();
+                        drop(interpolations);
+                        self.string()
+                    }
+                    Some(depth) => {
+                        *depth -= 1;
+                        self.put_token(TokenType::RightBrace)
+                    }
+                    None => self.put_token(TokenType::RightBrace),
+                }
+            }
             ',' => self.put_token(TokenType::Comma),
             '.' => self.put_token(TokenType::Dot),
             '-' => self.put_token(TokenType::Minus),
             '+' => self.put_token(TokenType::Plus),
             ';' => self.put_token(TokenType::Semicolon),
-            '*' => self.put_token(TokenType::Star),
+            '%' => self.put_token(TokenType::Percent),
+            '*' => self.put_token(if self.next_match('*') {
+                TokenType::StarStar
+            } else {
+                TokenType::Star
+            }),
+            '~' => self.put_token(if self.next_match('/') {
+                TokenType::TildeSlash
+            } else {
+                TokenType::Tilde
+            }),
+            '&' => self.put_token(TokenType::Ampersand),
+            '|' => self.put_token(TokenType::Pipe),
+            '^' => self.put_token(TokenType::Caret),
             '!' => self.put_token(if self.next_match('=') {
                 TokenType::BangEqual
             } else {
@@ -102,11 +172,15 @@ impl Scanner {
             }),
             '<' => self.put_token(if self.next_match('=') {
                 TokenType::LessEqual
+            } else if self.next_match('<') {
+                TokenType::LessLess
             } else {
                 TokenType::Less
             }),
             '>' => self.put_token(if self.next_match('=') {
                 TokenType::GreaterEqual
+            } else if self.next_match('>') {
+                TokenType::GreaterGreater
             } else {
                 TokenType::Greater
             }),
@@ -117,8 +191,7 @@ impl Scanner {
                     }
                     Ok(())
                 } else if self.next_match('*') {
-                    // multi line comments
-                    Ok(())
+                    self.block_comment()
                 } else {
                     self.put_token(TokenType::Slash)
                 }
@@ -132,16 +205,34 @@ impl Scanner {
                 } else if self.is_alpha(c) {
                     self.identifier()
                 } else {
-                    Err(LoxError::new(
-                        self.line.get(),
-                        None,
-                        "Unexpected character.",
-                    ))
+                    Err(LoxError::new(self.span(), None, "Unexpected character."))
                 }
             }
         }
     }
 
+    /// Skips a `/* ... */` comment. Block comments may span lines and nest.
+    fn block_comment(&self) -> Result<(), LoxError> {
+        let mut depth = 1;
+        while depth > 0 {
+            if self.end_of_source() {
+                // Point at the opening `/*` rather than the whole comment.
+                let span = Span {
+                    length: 2,
+                    ..self.span()
+                };
+                return Err(self.unterminated(span, "Unterminated block comment."));
+            }
+            match self.advance() {
+                '\n' => self.line_advance()?,
+                '/' if self.next_match('*') => depth += 1,
+                '*' if self.next_match('/') => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
     fn identifier(&self) -> Result<(), LoxError> {
         while self.is_alpha_numeric(self.peek()) {
             self.advance();
@@ -161,17 +252,18 @@ impl Scanner {
         while self.is_digit(self.peek()) {
             self.advance();
         }
-        let text = &self.source[self.start.get()..self.current.get()];
 
         let value = if self.peek() == '.' && self.is_digit(self.peek_next()) {
             self.advance();
             while self.is_digit(self.peek()) {
                 self.advance();
             }
+            let text = &self.source[self.start.get()..self.current.get()];
             text.parse::<f64>()
                 .map(Literal::Float)
                 .map_err(|e| format!("Failed to parse the value({}) to float. {}", text, e))
         } else {
+            let text = &self.source[self.start.get()..self.current.get()];
             text.parse::<i64>()
                 .map(Literal::Integer)
                 .map_err(|e| format!("Failed to parse the value({}) to integer. {}", text, e))
@@ -179,21 +271,78 @@ impl Scanner {
 
         match value {
             Ok(val) => self.put_token_with_literal(TokenType::Number, val),
-            Err(e) => Err(LoxError::new(self.line.get(), None, &e)),
+            Err(e) => Err(LoxError::new(self.span(), None, &e)),
         }
     }
 
+    /// Scans string text up to the closing quote, or up to a `${` that
+    /// starts an interpolated expression. In the latter case an
+    /// `Interpolation` token is produced and scanning of the string resumes
+    /// at the `}` that closes the expression.
     fn string(&self) -> Result<(), LoxError> {
-        self.advance_with_condition(|| self.peek() != '"')?;
+        let mut value = String::new();
+        loop {
+            if self.end_of_source() {
+                return Err(self.unterminated(self.span(), "Unterminated string."));
+            }
+            match self.advance() {
+                '"' => {
+                    return self.put_token_with_literal(TokenType::String, Literal::String(value))
+                }
+                '\\' => value.push(self.escape()?),
+                '$' if self.next_match('{') => {
+                    self.interpolations.borrow_mut().push(0);
+                    return self
+                        .put_token_with_literal(TokenType::Interpolation, Literal::String(value));
+                }
+                c => {
+                    if c == '\n' {
+                        self.line_advance()?;
+                    }
+                    value.push(c);
+                }
+            }
+        }
+    }
 
+    /// Decodes the escape sequence following a backslash.
+    fn escape(&self) -> Result<char, LoxError> {
+        let start = self.current.get() - 1;
         if self.end_of_source() {
-            return Err(LoxError::new(self.line.get(), None, "Unterminated string."));
+            return Err(self.unterminated(self.span(), "Unterminated string."));
         }
+        let c = match self.advance() {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            // Lets a literal `${` be written without starting an interpolation.
+            '$' => Some('$'),
+            'u' => return self.unicode_escape(start),
+            _ => None,
+        };
+        c.ok_or_else(|| LoxError::new(self.span_from(start), None, "Invalid escape sequence."))
+    }
 
-        self.advance();
-
-        let text = &self.source[(self.start.get() + 1)..(self.current.get() - 1)];
-        self.put_token_with_literal(TokenType::String, Literal::String(text.into()))
+    /// Decodes the `{XXXX}` part of a `\u{XXXX}` escape: one to six hex
+    /// digits naming a Unicode scalar value.
+    fn unicode_escape(&self, start: usize) -> Result<char, LoxError> {
+        let invalid = || LoxError::new(self.span_from(start), None, "Invalid Unicode escape.");
+        if !self.next_match('{') {
+            return Err(invalid());
+        }
+        let digits_start = self.current.get();
+        while self.peek().is_ascii_hexdigit() {
+            self.advance();
+        }
+        let digits = &self.source[digits_start..self.current.get()];
+        if !self.next_match('}') || digits.is_empty() || digits.len() > 6 {
+            return Err(invalid());
+        }
+        u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(invalid)
     }
 
     fn put_token(&self, ttype: TokenType) -> Result<(), LoxError> {
@@ -204,70 +353,94 @@ impl Scanner {
         let text = &self.source[self.start.get()..self.current.get()];
         self.tokens
             .borrow_mut()
-            .push(Token::new(ttype, text.into(), literal, self.line.get()));
+            .push(Token::new(ttype, text.into(), literal, self.span()));
+        self.token_end.set(Some(self.span_from(self.current.get())));
         Ok(())
     }
 
-    fn advance_with_condition<F>(&self, condition: F) -> Result<(), LoxError>
-    where
-        F: Fn() -> bool,
-    {
-        while condition() && !self.end_of_source() {
-            if self.end_of_line() {
-                self.line_advance()?;
-            }
-            self.advance();
+    fn start_token(&self) {
+        self.start.set(self.current.get());
+        self.start_line.set(self.line.get());
+        let line_prefix = &self.source[self.line_start.get()..self.start.get()];
+        self.start_column.set(line_prefix.chars().count() + 1);
+    }
+
+    /// An error for source that ends too early; see `is_unterminated`.
+    fn unterminated(&self, span: Span, message: &str) -> LoxError {
+        self.unterminated.set(true);
+        LoxError::new(span, None, message)
+    }
+
+    /// Span of the token scanned so far.
+    fn span(&self) -> Span {
+        Span {
+            offset: self.start.get(),
+            length: self.current.get() - self.start.get(),
+            line: self.start_line.get(),
+            column: self.start_column.get(),
+            source_id: self.source_id,
+        }
+    }
+
+    /// Span from `offset` on the current line up to the current position.
+    fn span_from(&self, offset: usize) -> Span {
+        let line_prefix = &self.source[self.line_start.get()..offset];
+        Span {
+            offset,
+            length: self.current.get() - offset,
+            line: self.line.get(),
+            column: line_prefix.chars().count() + 1,
+            source_id: self.source_id,
         }
-        Ok(())
     }
 
     fn advance(&self) -> char {
-        let c = self.source_chars[self.current.get()];
-        self.current.set(self.current.get() + 1);
+        let c = self.peek();
+        self.current.set(self.current.get() + c.len_utf8());
         c
     }
 
     fn line_advance(&self) -> Result<(), LoxError> {
         self.line.set(self.line.get() + 1);
+        self.line_start.set(self.current.get());
         Ok(())
     }
 
     fn peek(&self) -> char {
-        if self.end_of_source() {
-            return '\0';
-        }
-        self.source_chars[self.current.get()]
+        self.source[self.current.get()..]
+            .chars()
+            .next()
+            .unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        let next = self.current.get() + 1;
-        if next >= self.source.len() {
-            return '\0';
-        }
-        self.source_chars[next]
+        self.source[self.current.get()..]
+            .chars()
+            .nth(1)
+            .unwrap_or('\0')
     }
 
     fn next_match(&self, expected: char) -> bool {
         if self.end_of_source() {
             return false;
         }
-        if self.source_chars[self.current.get()] != expected {
+        if self.peek() != expected {
             return false;
         }
         self.advance();
-        return true;
+        true
     }
 
     fn is_alpha_numeric(&self, c: char) -> bool {
-        self.is_alpha(c) || self.is_digit(c)
+        self.is_alpha(c) || self.is_digit(c) || is_xid_continue(c)
     }
 
     fn is_alpha(&self, c: char) -> bool {
-        (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_'
+        c.is_ascii_alphabetic() || c == '_' || is_xid_start(c)
     }
 
     fn is_digit(&self, c: char) -> bool {
-        c >= '0' && c <= '9'
+        c.is_ascii_digit()
     }
 
     fn end_of_line(&self) -> bool {
@@ -278,3 +451,138 @@ impl Scanner {
         self.current.get() >= self.source.len()
     }
 }
+
+#[cfg(feature = "unicode-identifiers")]
+fn is_xid_start(c: char) -> bool {
+    unicode_ident::is_xid_start(c)
+}
+
+#[cfg(feature = "unicode-identifiers")]
+fn is_xid_continue(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
+}
+
+#[cfg(not(feature = "unicode-identifiers"))]
+fn is_xid_start(_c: char) -> bool {
+    false
+}
+
+#[cfg(not(feature = "unicode-identifiers"))]
+fn is_xid_continue(_c: char) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(source: &str) -> Result<Vec<Token>, LoxError> {
+        Scanner::new(source.to_string()).scan()
+    }
+
+    #[test]
+    fn strings_may_hold_any_unicode() {
+        let tokens = scan("print \"héllo, 世界 🦀\"; x").unwrap();
+        let Literal::String(text) = &tokens[1].literal else {
+            panic!("expected a string, got {:?}", tokens[1]);
+        };
+        assert_eq!(text, "héllo, 世界 🦀");
+        assert_eq!(tokens[1].lexeme, "\"héllo, 世界 🦀\"");
+        // Columns count characters, not bytes.
+        assert_eq!(tokens[3].lexeme, "x");
+        assert_eq!((tokens[3].span.line, tokens[3].span.column), (1, 22));
+    }
+
+    #[test]
+    fn comments_may_hold_any_unicode() {
+        let tokens = scan("// ünïcödé ✓\n/* 世界 /* 🦀 */ */ x").unwrap();
+        assert_eq!(tokens[0].lexeme, "x");
+        assert_eq!((tokens[0].span.line, tokens[0].span.column), (2, 18));
+        assert_eq!(tokens[1].ttype, TokenType::Eof);
+    }
+
+    #[test]
+    fn errors_after_unicode_report_character_columns() {
+        let error = scan("\"日本\" @").unwrap_err();
+        assert_eq!(error.message, "Unexpected character.");
+        assert_eq!((error.span.line, error.span.column), (1, 6));
+
+        let error = scan("var s = \"ü\";\n  \"ß").unwrap_err();
+        assert_eq!(error.message, "Unterminated string.");
+        assert_eq!((error.span.line, error.span.column), (2, 3));
+    }
+
+    #[cfg(not(feature = "unicode-identifiers"))]
+    #[test]
+    fn identifiers_are_ascii_by_default() {
+        let error = scan("var größe;").unwrap_err();
+        assert_eq!(error.message, "Unexpected character.");
+        assert_eq!(error.span.column, 7);
+    }
+
+    #[cfg(feature = "unicode-identifiers")]
+    #[test]
+    fn identifiers_follow_xid_rules() {
+        let tokens = scan("var größe = π_2;").unwrap();
+        assert_eq!(tokens[1].ttype, TokenType::Identifier);
+        assert_eq!(tokens[1].lexeme, "größe");
+        assert_eq!(tokens[3].lexeme, "π_2");
+        // Not XID_Start, so still rejected.
+        assert_eq!(scan("🦀").unwrap_err().message, "Unexpected character.");
+    }
+
+    #[test]
+    fn escapes_in_strings() {
+        let tokens = scan(r#""a\nb\t\"q\" \\ \u{48}\u{1F980} \${x}""#).unwrap();
+        let Literal::String(text) = &tokens[0].literal else {
+            panic!("expected a string, got {:?}", tokens[0]);
+        };
+        assert_eq!(text, "a\nb\t\"q\" \\ H🦀 ${x}");
+    }
+
+    #[test]
+    fn invalid_escapes_are_errors() {
+        let error = scan(r#"print "ok \q";"#).unwrap_err();
+        assert_eq!(error.message, "Invalid escape sequence.");
+        assert_eq!((error.span.column, error.span.length), (11, 2));
+
+        for source in [
+            r#""\u48""#,
+            r#""\u{}""#,
+            r#""\u{48""#,
+            r#""\u{1234567}""#,
+            r#""\u{110000}""#,
+            r#""\u{D800}""#,
+        ] {
+            let error = scan(source).unwrap_err();
+            assert_eq!(error.message, "Invalid Unicode escape.", "{}", source);
+            assert_eq!(error.span.column, 2, "{}", source);
+        }
+    }
+
+    #[test]
+    fn interpolations_nest() {
+        let tokens = scan(r#""a ${ "b ${x} c" } d""#).unwrap();
+        let types: Vec<TokenType> = tokens.iter().map(|token| token.ttype).collect();
+        assert_eq!(
+            types,
+            [
+                TokenType::Interpolation,
+                TokenType::Interpolation,
+                TokenType::Identifier,
+                TokenType::String,
+                TokenType::String,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn end_of_input_follows_the_last_token() {
+        let tokens = scan("print 1\n\n  // done\n").unwrap();
+        let eof = tokens.last().unwrap();
+        assert_eq!(eof.ttype, TokenType::Eof);
+        assert_eq!((eof.span.line, eof.span.column, eof.span.offset), (1, 8, 7));
+        assert_eq!(eof.span.length, 0);
+    }
+}
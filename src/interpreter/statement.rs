@@ -1,25 +1,86 @@
-use super::{expression::Expr, token::Token};
+use std::rc::Rc;
 
+use super::{
+    expression::Expr,
+    token::{Span, Token},
+};
+
+#[derive(Debug)]
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Statement>,
+}
+
+/// A statement. The last field of every variant is the span of the whole
+/// statement, from its first token to its closing `;` or `}`.
 #[derive(Debug)]
 pub enum Statement {
-    Print(Box<Expr>),
-    Expression(Box<Expr>),
-    Var(Token, Option<Expr>),
+    Block(Vec<Statement>, Span),
+    Class(Token, Option<Expr>, Vec<Rc<FunctionDecl>>, Span),
+    Print(Box<Expr>, Span),
+    Expression(Box<Expr>, Span),
+    Function(Rc<FunctionDecl>, Span),
+    If(Box<Expr>, Box<Statement>, Option<Box<Statement>>, Span),
+    Return(Token, Option<Expr>, Span),
+    Var(Token, Option<Expr>, Span),
+    While(Box<Expr>, Box<Statement>, Span),
 }
 
 impl Statement {
     pub fn accept<V: StmtVisitor>(&self, visitor: &V) -> V::Res {
         match self {
-            Statement::Print(expr) => visitor.visit_print_stmt(expr),
-            Statement::Expression(expr) => visitor.visit_expression_stmt(expr),
-            Statement::Var(name, expr) => visitor.visit_var_stmt(name, expr),
+            Statement::Block(statements, _) => visitor.visit_block_stmt(statements),
+            Statement::Class(name, superclass, methods, _) => {
+                visitor.visit_class_stmt(name, superclass, methods)
+            }
+            Statement::Print(expr, _) => visitor.visit_print_stmt(expr),
+            Statement::Expression(expr, _) => visitor.visit_expression_stmt(expr),
+            Statement::Function(declaration, _) => visitor.visit_function_stmt(declaration),
+            Statement::If(condition, then_branch, else_branch, _) => {
+                visitor.visit_if_stmt(condition, then_branch, else_branch)
+            }
+            Statement::Return(keyword, value, _) => visitor.visit_return_stmt(keyword, value),
+            Statement::Var(name, expr, _) => visitor.visit_var_stmt(name, expr),
+            Statement::While(condition, body, _) => visitor.visit_while_stmt(condition, body),
+        }
+    }
+
+    /// The source text this statement was parsed from.
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Block(_, span)
+            | Statement::Class(_, _, _, span)
+            | Statement::Print(_, span)
+            | Statement::Expression(_, span)
+            | Statement::Function(_, span)
+            | Statement::If(_, _, _, span)
+            | Statement::Return(_, _, span)
+            | Statement::Var(_, _, span)
+            | Statement::While(_, _, span) => *span,
         }
     }
 }
 
 pub trait StmtVisitor {
     type Res;
-    fn visit_print_stmt(&self, expr: &Box<Expr>) -> Self::Res;
-    fn visit_expression_stmt(&self, expr: &Box<Expr>) -> Self::Res;
+    fn visit_block_stmt(&self, statements: &[Statement]) -> Self::Res;
+    fn visit_class_stmt(
+        &self,
+        name: &Token,
+        superclass: &Option<Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> Self::Res;
+    fn visit_print_stmt(&self, expr: &Expr) -> Self::Res;
+    fn visit_expression_stmt(&self, expr: &Expr) -> Self::Res;
+    fn visit_function_stmt(&self, declaration: &Rc<FunctionDecl>) -> Self::Res;
+    fn visit_if_stmt(
+        &self,
+        condition: &Expr,
+        then_branch: &Statement,
+        else_branch: &Option<Box<Statement>>,
+    ) -> Self::Res;
+    fn visit_return_stmt(&self, keyword: &Token, value: &Option<Expr>) -> Self::Res;
     fn visit_var_stmt(&self, name: &Token, initializer: &Option<Expr>) -> Self::Res;
+    fn visit_while_stmt(&self, condition: &Expr, body: &Statement) -> Self::Res;
 }
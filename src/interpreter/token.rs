@@ -1,20 +1,55 @@
 use super::token_type::{Literal, TokenType};
 
+/// A region of source text.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Span {
+    /// Byte offset of the first character.
+    pub offset: usize,
+    /// Length in bytes.
+    pub length: usize,
+    /// 1-based line of the first character.
+    pub line: usize,
+    /// 1-based column of the first character, counted in characters.
+    pub column: usize,
+    /// Which source text the span points into. The interpreter numbers each
+    /// `run` or `eval` call from 1; 0 means no particular source.
+    pub source_id: usize,
+}
+
+impl Span {
+    /// The smallest span covering both `self` and `other`.
+    pub fn merge(&self, other: Span) -> Span {
+        let first = if self.offset <= other.offset {
+            *self
+        } else {
+            other
+        };
+        let end = (self.offset + self.length).max(other.offset + other.length);
+        Span {
+            offset: first.offset,
+            length: end - first.offset,
+            line: first.line,
+            column: first.column,
+            source_id: first.source_id,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub ttype: TokenType,
     pub lexeme: String,
     pub literal: Literal,
-    pub line: usize,
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(ttype: TokenType, lexeme: String, literal: Literal, line: usize) -> Self {
+    pub fn new(ttype: TokenType, lexeme: String, literal: Literal, span: Span) -> Self {
         Token {
             ttype,
             lexeme,
             literal,
-            line,
+            span,
         }
     }
 }
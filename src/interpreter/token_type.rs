@@ -21,6 +21,11 @@ pub enum TokenType {
     Semicolon,
     Slash,
     Star,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
 
     // One or two character tokens.
     Bang,
@@ -31,10 +36,17 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    StarStar,
+    LessLess,
+    GreaterGreater,
+    /// `~/`, floor division. `//` already starts a comment.
+    TildeSlash,
 
     // Literals.
     Identifier,
     String,
+    /// String text that ends in `${`, followed by the interpolated expression.
+    Interpolation,
     Number,
 
     // Keywords.
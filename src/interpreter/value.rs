@@ -0,0 +1,94 @@
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use super::{
+    callable::{LoxFunction, NativeFunction},
+    class::{LoxClass, LoxInstance},
+    token_type::Literal,
+};
+
+/// A value produced while running a program. Unlike `Literal`, which only
+/// covers the constants the scanner can read from source, this also holds
+/// heap objects shared by reference.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Function(Rc<LoxFunction>),
+    NativeFunction(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<LoxInstance>),
+    /// A list built by host code, shared and mutable like other objects.
+    List(Rc<RefCell<Vec<Value>>>),
+    /// A string-keyed map built by host code.
+    Map(Rc<RefCell<HashMap<String, Value>>>),
+}
+
+impl Value {
+    /// Name of the value's type, as reported by the `type()` native.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "bool",
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Function(_) | Value::NativeFunction(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+        }
+    }
+}
+
+impl From<&Literal> for Value {
+    fn from(literal: &Literal) -> Self {
+        match literal {
+            Literal::Float(val) => Value::Float(*val),
+            Literal::Integer(val) => Value::Integer(*val),
+            Literal::String(val) => Value::String(val.clone()),
+            Literal::Bool(val) => Value::Bool(*val),
+            Literal::None => Value::Nil,
+        }
+    }
+}
+
+/// Formats values the way reference Lox prints them: strings without quotes,
+/// whole floats without a trailing `.0`, and objects by name.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(val) => write!(f, "{}", val),
+            Value::Integer(val) => write!(f, "{}", val),
+            // Spelled as in JavaScript rather than Rust's `inf` and `NaN`.
+            Value::Float(val) if val.is_nan() => write!(f, "NaN"),
+            Value::Float(val) if val.is_infinite() => {
+                write!(f, "{}Infinity", if *val < 0.0 { "-" } else { "" })
+            }
+            Value::Float(val) => write!(f, "{}", val),
+            Value::String(val) => write!(f, "{}", val),
+            Value::Function(function) => write!(f, "{:?}", function),
+            Value::NativeFunction(function) => write!(f, "{:?}", function),
+            Value::Class(class) => write!(f, "{}", class.name),
+            Value::Instance(instance) => write!(f, "{:?}", instance),
+            Value::List(list) => {
+                let items: Vec<String> = list.borrow().iter().map(Value::to_string).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Value::Map(map) => {
+                let map = map.borrow();
+                let mut entries: Vec<String> = map
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect();
+                // Sorted so that printing a map is deterministic.
+                entries.sort();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+        }
+    }
+}
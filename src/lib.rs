@@ -1,30 +1,308 @@
-use std::path::Path;
+use std::{
+    cell::RefCell,
+    fs,
+    io::{self, BufRead, Write},
+    path::Path,
+    process::ExitCode,
+    rc::Rc,
+};
 
 #[macro_use]
 extern crate log;
 extern crate env_logger;
 
 mod interpreter;
-use interpreter::Interpreter;
+pub use interpreter::{
+    ConversionError, FromLox, Interpreter, IntoLox, IntoLoxArgs, LoxClass, LoxError, LoxFunction,
+    LoxInstance, NativeFn, NativeFunction, RunError, RuntimeError, Span, Value,
+    DEFAULT_MAX_CALL_DEPTH,
+};
+
+/// Exit status for scripts that fail to scan or parse (sysexits `EX_DATAERR`).
+pub const EXIT_COMPILE_ERROR: u8 = 65;
+/// Exit status for scripts that fail while running (sysexits `EX_SOFTWARE`).
+pub const EXIT_RUNTIME_ERROR: u8 = 70;
+/// Exit status for script files that cannot be read (sysexits `EX_IOERR`).
+pub const EXIT_IO_ERROR: u8 = 74;
+
 pub struct RustLox {
     interpreter: Interpreter,
 }
 
+impl Default for RustLox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RustLox {
     pub fn new() -> Self {
         RustLox {
             interpreter: Interpreter::new(),
         }
     }
+    /// Sends program output to `output` instead of stdout.
+    pub fn with_output(self, output: Rc<RefCell<dyn Write>>) -> Self {
+        RustLox {
+            interpreter: self.interpreter.with_output(output),
+        }
+    }
+
+    /// Sends error reports from `run_with_prompt`/`run_with_file` to
+    /// `diagnostics` instead of stderr.
+    pub fn with_diagnostics(self, diagnostics: Rc<RefCell<dyn Write>>) -> Self {
+        RustLox {
+            interpreter: self.interpreter.with_diagnostics(diagnostics),
+        }
+    }
+
+    /// Reads `input()` lines from `input` instead of stdin.
+    pub fn with_input(self, input: Rc<RefCell<dyn BufRead>>) -> Self {
+        RustLox {
+            interpreter: self.interpreter.with_input(input),
+        }
+    }
+
+    /// Stops scripts whose calls nest more than `depth` deep with a "Stack
+    /// overflow." runtime error instead of the default
+    /// `DEFAULT_MAX_CALL_DEPTH`.
+    pub fn with_max_call_depth(self, depth: usize) -> Self {
+        RustLox {
+            interpreter: self.interpreter.with_max_call_depth(depth),
+        }
+    }
+
+    /// Reads the next line from the interpreter's input, or `None` at the end
+    /// of input.
+    pub fn read_line(&self) -> io::Result<Option<String>> {
+        self.interpreter.read_line()
+    }
+
+    /// Whether `source` needs more lines before it can be run, e.g. because
+    /// a brace is still open.
+    pub fn is_incomplete(source: &str) -> bool {
+        Interpreter::is_incomplete(source)
+    }
+
+    /// Runs one REPL entry. The final expression may leave out its `;`, and
+    /// its value is echoed unless it is nil.
     pub fn run_with_prompt(&self, prompt: String) {
         info!("Run with prompt: {prompt}");
-        let result = self.interpreter.run(prompt);
-        if let Err(res) = result {
-            println!("{}", res);
+        match self.interpreter.eval(&prompt) {
+            Ok(Value::Nil) => {}
+            Ok(value) => {
+                if let Err(e) = self.interpreter.print(&value) {
+                    self.interpreter
+                        .report(&format!("Failed to write output. {}", e));
+                }
+            }
+            Err(error) => self.interpreter.report(&error),
         }
     }
 
-    pub fn run_with_file(&self, file_path: &Path) {
+    /// Runs `source` and returns the value of its last statement if that is
+    /// an expression (e.g. `"1 + 2"`), or nil otherwise. Errors are returned,
+    /// never printed.
+    pub fn eval(&self, source: &str) -> Result<Value, RunError> {
+        self.interpreter.eval(source)
+    }
+
+    /// Like `eval`, converting the value to `T`.
+    pub fn eval_as<T: FromLox>(&self, source: &str) -> Result<T, RunError> {
+        let value = self.interpreter.eval(source)?;
+        Ok(T::from_lox(&value).map_err(RuntimeError::from)?)
+    }
+
+    /// The value of the global variable `name`, if it is defined.
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.interpreter.get_global(name)
+    }
+
+    /// Defines or overwrites the global variable `name`.
+    pub fn set_global(&self, name: &str, value: impl IntoLox) {
+        self.interpreter.set_global(name, value.into_lox())
+    }
+
+    /// Calls the Lox function (or class) stored in the global `name` with a
+    /// tuple of arguments, e.g. `call::<i64>("add", (1, 2))`, and converts
+    /// the result to `R`.
+    pub fn call<R: FromLox>(
+        &self,
+        name: &str,
+        arguments: impl IntoLoxArgs,
+    ) -> Result<R, RuntimeError> {
+        let value = self
+            .interpreter
+            .call_global(name, arguments.into_lox_args())?;
+        Ok(R::from_lox(&value)?)
+    }
+
+    /// Makes a Rust function callable from Lox as the global `name`. `body`
+    /// is only ever called with exactly `arity` arguments.
+    pub fn define_native<F>(&self, name: &str, arity: usize, body: F)
+    where
+        F: Fn(&Interpreter, &[Value]) -> Result<Value, RuntimeError> + 'static,
+    {
+        self.interpreter.define_native(name, arity, body)
+    }
+
+    /// Makes a Rust closure over ordinary Rust types callable from Lox as the
+    /// global `name`, e.g. `define_fn("add", |a: i64, b: i64| Ok(a + b))`.
+    pub fn define_fn<Args, F: NativeFn<Args>>(&self, name: &str, body: F) {
+        self.interpreter.define_fn(name, body)
+    }
+
+    pub fn run_with_file(&self, file_path: &Path) -> ExitCode {
         info!("Run with file_path: {:?}", file_path);
+        let source = match fs::read_to_string(file_path) {
+            Ok(source) => source,
+            Err(e) => {
+                self.interpreter.report(&format!(
+                    "{}: Could not read file. {}",
+                    file_path.display(),
+                    e
+                ));
+                return ExitCode::from(EXIT_IO_ERROR);
+            }
+        };
+
+        match self.interpreter.run(source) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(RunError::Compile(errors)) => {
+                for error in errors {
+                    self.interpreter
+                        .report(&format!("{}: {}", file_path.display(), error));
+                }
+                ExitCode::from(EXIT_COMPILE_ERROR)
+            }
+            Err(RunError::Runtime(error)) => {
+                self.interpreter
+                    .report(&format!("{}: {}", file_path.display(), error));
+                ExitCode::from(EXIT_RUNTIME_ERROR)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, io::Cursor};
+
+    use super::*;
+
+    #[test]
+    fn host_functions_take_and_return_rust_types() {
+        let lox = RustLox::new();
+        lox.define_fn("add", |a: i64, b: i64| Ok(a + b));
+        lox.define_fn("greet", |name: String, excited: Option<bool>| {
+            let mark = if excited.unwrap_or(false) { "!" } else { "." };
+            Ok(format!("Hello, {}{}", name, mark))
+        });
+        lox.define_fn("sum", |numbers: Vec<f64>| Ok(numbers.iter().sum::<f64>()));
+        lox.define_fn("answer", || Ok(42));
+
+        assert_eq!(lox.eval_as::<i64>("add(2, 3)").unwrap(), 5);
+        assert_eq!(
+            lox.eval_as::<String>("greet(\"Lox\", true)").unwrap(),
+            "Hello, Lox!"
+        );
+        assert_eq!(
+            lox.eval_as::<String>("greet(\"Lox\", nil)").unwrap(),
+            "Hello, Lox."
+        );
+        lox.set_global("numbers", vec![1.0, 2.5]);
+        assert_eq!(lox.eval_as::<f64>("sum(numbers)").unwrap(), 3.5);
+        assert_eq!(lox.eval_as::<i64>("answer()").unwrap(), 42);
+
+        let error = lox.eval("add(1, \"2\")").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 1:1] Runtime error: Argument 2: Expected integer but got string.\n \
+             1 | add(1, \"2\")\n   | ^^^^^^^^^^^"
+        );
+    }
+
+    #[test]
+    fn lox_functions_are_called_with_rust_values() {
+        let lox = RustLox::new();
+        lox.eval("fun count(items) { return len(items); } fun join(a, b) { return a + b; }")
+            .unwrap();
+
+        let count: i64 = lox.call("count", (vec![1, 2, 3],)).unwrap();
+        assert_eq!(count, 3);
+        let count: i64 = lox
+            .call("count", (HashMap::from([("one".to_string(), 1)]),))
+            .unwrap();
+        assert_eq!(count, 1);
+        let joined: String = lox.call("join", ("Lox", String::from("!"))).unwrap();
+        assert_eq!(joined, "Lox!");
+        let joined: f64 = lox.call("join", vec![1.5, 2.0]).unwrap();
+        assert_eq!(joined, 3.5);
+
+        let error = lox.call::<String>("count", (vec![1],)).unwrap_err();
+        assert_eq!(error.message, "Expected string but got integer.");
+        let error = lox.call::<Value>("missing", ()).unwrap_err();
+        assert_eq!(error.message, "Undefined variable 'missing'.");
+    }
+
+    /// A shared buffer to pass as an output or diagnostics stream.
+    fn buffer() -> Rc<RefCell<Vec<u8>>> {
+        Rc::new(RefCell::new(Vec::new()))
+    }
+
+    fn text(buffer: &RefCell<Vec<u8>>) -> String {
+        String::from_utf8(buffer.take()).unwrap()
+    }
+
+    #[test]
+    fn input_reads_lines_from_the_redirected_input() {
+        let printed = buffer();
+        let lox = RustLox::new()
+            .with_output(printed.clone())
+            .with_input(Rc::new(RefCell::new(Cursor::new("Ada\r\nBob\n"))));
+        lox.eval("print input(); print input(); print input();")
+            .unwrap();
+        assert_eq!(text(&printed), "Ada\nBob\nnil\n");
+    }
+
+    #[test]
+    fn errors_are_reported_to_the_diagnostics_stream() {
+        let printed = buffer();
+        let reported = buffer();
+        let lox = RustLox::new()
+            .with_output(printed.clone())
+            .with_diagnostics(reported.clone());
+
+        lox.run_with_prompt("1 + 2".to_string());
+        assert_eq!(text(&printed), "3\n");
+        // The REPL passes entries on with their final newline.
+        lox.run_with_prompt("print\n".to_string());
+        assert_eq!(
+            text(&reported),
+            "[line 1:6] Error: Expect expression.\n 1 | print\n   |      ^\n"
+        );
+        lox.run_with_prompt("-nil".to_string());
+        assert_eq!(
+            text(&reported),
+            "[line 1:1] Runtime error: Operand must be a number.\n 1 | -nil\n   | ^^^^\n"
+        );
+
+        let path = std::env::temp_dir().join(format!("rustlox-test-{}.lox", std::process::id()));
+        fs::write(&path, "print 1;\nprint ;").unwrap();
+        let status = lox.run_with_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(status, ExitCode::from(EXIT_COMPILE_ERROR));
+        assert_eq!(
+            text(&reported),
+            format!(
+                "{}: [line 2:7] Error ';': Expect expression.\n 2 | print ;\n   |       ^\n",
+                path.display()
+            )
+        );
+        assert_eq!(text(&printed), "");
+
+        let status = lox.run_with_file(&path);
+        assert_eq!(status, ExitCode::from(EXIT_IO_ERROR));
+        assert!(text(&reported).starts_with(&format!("{}: Could not read file.", path.display())));
     }
 }
@@ -1,4 +1,6 @@
+use std::process::ExitCode;
+
 mod cli;
-fn main() {
-    cli::Cli::run();
+fn main() -> ExitCode {
+    cli::Cli::run()
 }